@@ -2,6 +2,7 @@ use std::fs::OpenOptions;
 use std::mem;
 use std::mem::size_of;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use memmap2::{Mmap, MmapMut, MmapOptions};
 
@@ -19,6 +20,19 @@ pub fn create_and_ensure_length(path: &Path, length: usize) -> OperationResult<(
     Ok(())
 }
 
+/// Create a file of `length` zero bytes, replacing any existing file, and open it as write mmap.
+pub fn create_zeroed_mmap(path: &Path, length: usize) -> OperationResult<MmapMut> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+
+    file.set_len(length as u64)?;
+    open_write_mmap(path)
+}
+
 pub fn open_read_mmap(path: &Path) -> OperationResult<Mmap> {
     let file = OpenOptions::new()
         .read(true)
@@ -44,6 +58,77 @@ pub fn open_write_mmap(path: &Path) -> OperationResult<MmapMut> {
     Ok(mmap)
 }
 
+/// Open a write mmap of `length` bytes of the file, starting at byte `offset`.
+///
+/// The offset must be a multiple of the page size, and of the allocation granularity on Windows.
+pub fn open_write_mmap_range(
+    path: &Path,
+    offset: usize,
+    length: usize,
+) -> OperationResult<MmapMut> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(false)
+        .open(path)?;
+
+    let mmap = unsafe {
+        MmapOptions::new()
+            .offset(offset as u64)
+            .len(length)
+            .map_mut(&file)?
+    };
+    madvise::madvise(&mmap, madvise::get_global())?;
+    Ok(mmap)
+}
+
+/// Open a private copy-on-write mmap, of which changes are never written to the file.
+pub fn open_cow_mmap(path: &Path) -> OperationResult<MmapMut> {
+    let file = OpenOptions::new().read(true).open(path)?;
+
+    let mmap = unsafe { MmapOptions::new().map_copy(&file)? };
+    madvise::madvise(&mmap, madvise::get_global())?;
+    Ok(mmap)
+}
+
+/// Page size of the OS in bytes
+///
+/// Queried once and cached. Use this rather than assuming 4 KiB pages, some systems have larger
+/// pages.
+pub fn page_size() -> usize {
+    // Zero until queried, the page size is never zero
+    static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+    match PAGE_SIZE.load(Ordering::Relaxed) {
+        0 => {
+            let page_size = query_page_size();
+            PAGE_SIZE.store(page_size, Ordering::Relaxed);
+            page_size
+        }
+        page_size => page_size,
+    }
+}
+
+#[cfg(unix)]
+fn query_page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(windows)]
+fn query_page_size() -> usize {
+    use windows_sys::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+
+    let mut info: SYSTEM_INFO = unsafe { mem::zeroed() };
+    unsafe { GetSystemInfo(&mut info) };
+    info.dwPageSize as usize
+}
+
+/// Assumes the smallest common page size on platforms other than Unix and Windows.
+#[cfg(not(any(unix, windows)))]
+fn query_page_size() -> usize {
+    4096
+}
+
 pub fn transmute_to_u8<T>(v: &T) -> &[u8] {
     unsafe { std::slice::from_raw_parts(v as *const T as *const u8, mem::size_of_val(v)) }
 }
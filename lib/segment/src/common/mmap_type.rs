@@ -7,6 +7,9 @@
 //! - [`MmapType`]
 //! - [`MmapSlice`]
 //! - [`MmapBitSlice`]
+//! - [`MmapHeaderSlice`]
+//! - [`MmapChunkedSlice`]
+//! - [`MmapTypeReadOnly`]
 //!
 //! Various additional functions are added for use within Qdrant, such as `flusher` to obtain a
 //! flusher handle to explicitly flush the underlying memory map at a later time.
@@ -22,20 +25,48 @@
 //! utmost care. Security is critical here as this is an easy place to introduce undefined
 //! behavior. Problems caused by this are very hard to debug.
 
-use std::ops::{Deref, DerefMut};
+use std::io;
+use std::iter;
+use std::ops::{Deref, DerefMut, Range};
+use std::path::Path;
 #[cfg(windows)]
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::{mem, slice};
 
 use bitvec::slice::BitSlice;
-use memmap2::MmapMut;
+use bytemuck::Pod;
+use crc::{Crc, Digest, CRC_32_ISCSI};
+use memmap2::{Mmap, MmapMut};
+use num_traits::Float;
+use parking_lot::Mutex;
 
-use crate::common::Flusher;
+use crate::common::{combine_flushers, mmap_ops, Flusher};
+use crate::entry::entry_point::{OperationError, OperationResult};
+use crate::madvise::{self, Advice, Madviseable};
+use crate::vector_storage::div_ceil;
 
 /// Result for mmap errors.
 type Result<T> = std::result::Result<T, Error>;
 
+/// Size in bytes of the checksum header of a checksummed typed mmap
+///
+/// The header holds a little endian CRC32C, and is padded to keep the type after it aligned.
+pub const CHECKSUM_HEADER_SIZE: usize = 8;
+
+/// Number of values checked at once by [`MmapSlice::validate_finite`]
+const VALIDATE_CHUNK_SIZE: usize = 16;
+
+/// Maximum size in bytes of a mmap to populate, larger mmaps are not populated
+pub const MAX_POPULATE_SIZE: usize = 1024 * 1024 * 1024;
+
+/// Distance in bytes between touched bytes when populating, at most the smallest page size
+const POPULATE_STRIDE: usize = 4096;
+
+/// CRC32C (Castagnoli) used to checksum typed mmaps
+static CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
 /// Type `T` on a memory mapped file
 ///
 /// Functions as if it is `T` because this implements [`Deref`] and [`DerefMut`].
@@ -72,6 +103,42 @@ where
     /// `r#type`. That must be used instead. The sole purpose of this is to keep ownership of the
     /// mmap, and to allow properly cleaning up when this struct is dropped.
     mmap: Arc<MmapMut>,
+    /// Checksum accessor: mutable reference to the checksum header, if any
+    ///
+    /// # Safety
+    ///
+    /// Like `r#type`, this is an alias to the data inside `mmap`. It never overlaps `r#type`.
+    checksum: Option<&'static mut u32>,
+    /// Whether to overwrite the data with zeros when dropped
+    zero_on_drop: bool,
+    /// Whether `mmap` is a private copy-on-write mapping, of which changes are never persisted
+    copy_on_write: bool,
+    /// Advice set for this mmap, applied again when remapping. Uses the global advice if not set.
+    advice: Option<Advice>,
+    /// State of the checksum pass in progress by [`MmapType::scrub`], if any
+    scrub: Mutex<Option<ScrubState>>,
+}
+
+/// Progress of incremental checksum verification, see [`MmapType::scrub`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScrubProgress {
+    /// Verified `offset` out of `len` bytes so far, call again to continue
+    InProgress { offset: usize, len: usize },
+    /// All bytes were verified and match the checksum header
+    Verified,
+    /// All bytes were verified, but they don't match the checksum header
+    Corrupted { stored: u32, computed: u32 },
+    /// There is no checksum header to verify against
+    NoChecksum,
+}
+
+/// Rolling state of a checksum pass by [`MmapType::scrub`]
+struct ScrubState {
+    /// Stored checksum at the start of the pass, the pass restarts if it changes
+    expected: u32,
+    /// Number of bytes digested so far
+    offset: usize,
+    digest: Digest<'static, u32>,
 }
 
 impl<T> MmapType<T>
@@ -89,28 +156,257 @@ where
     ///
     /// - panics when the size of the mmap doesn't match size `T`
     /// - panics when the mmap data is not correctly aligned for type `T`
-    /// - See: [`mmap_to_type_unbounded`]
+    /// - See: [`MmapType::try_from`]
     pub unsafe fn from(mmap_with_type: MmapMut) -> Self {
         Self::try_from(mmap_with_type).unwrap()
     }
 
     /// Transform a mmap into a typed mmap of type `T`.
     ///
-    /// Returns an error when the mmap has an incorrect size.
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn try_from(mut mmap_with_type: MmapMut) -> Result<Self> {
+        let r#type = mmap_to_type_unbounded(&mut mmap_with_type)?;
+        let mmap = shared_mmap(mmap_with_type);
+        Ok(Self {
+            r#type,
+            mmap,
+            checksum: None,
+            zero_on_drop: false,
+            copy_on_write: false,
+            advice: None,
+            scrub: Mutex::new(None),
+        })
+    }
+
+    /// Create a zeroed file at `path` sized to fit type `T`, and map it as type `T`.
+    ///
+    /// Replaces any existing file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because zeroed data may break type `T` resulting in undefined behavior.
+    pub unsafe fn create(path: &Path) -> OperationResult<Self> {
+        let mmap = mmap_ops::create_zeroed_mmap(path, mem::size_of::<T>())?;
+        Ok(Self::try_from(mmap)?)
+    }
+
+    /// Transform a mmap into a typed mmap of type `T`, that is overwritten with zeros when dropped.
+    ///
+    /// Intended for sensitive data that must not linger in memory. The zeros are written through
+    /// the mmap, and are flushed when dropped. For a shared file mapping this erases the data in
+    /// the file as well. To only clear memory and keep the file as is, use a copy-on-write or
+    /// anonymous mapping, see [`memmap2::MmapOptions::map_copy`] and [`MmapMut::map_anon`].
+    ///
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
     ///
     /// # Safety
     ///
     /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
     /// behavior.
+    pub unsafe fn try_from_zero_on_drop(mmap_with_type: MmapMut) -> Result<Self> {
+        let mut mmap = Self::try_from(mmap_with_type)?;
+        mmap.zero_on_drop = true;
+        Ok(mmap)
+    }
+
+    /// Map the file at `path` as type `T` with a private copy-on-write mapping.
+    ///
+    /// Changes stay in memory and are never written to the file. Flushing is therefore a no-op.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn from_cow(path: &Path) -> OperationResult<Self> {
+        let mmap = mmap_ops::open_cow_mmap(path)?;
+        let mut mmap = Self::try_from(mmap)?;
+        mmap.copy_on_write = true;
+        Ok(mmap)
+    }
+
+    /// Transform a mmap into a typed mmap of type `T`, verifying its checksum header.
+    ///
+    /// The mmap must start with a header of [`CHECKSUM_HEADER_SIZE`] bytes holding a CRC32C over
+    /// the bytes of `T`, as written by [`MmapType::new_with_checksum`] and
+    /// [`MmapType::update_checksum`].
+    ///
+    /// Returns an error when the checksum does not match, when the mmap has an incorrect size, or
+    /// when the mmap data is not correctly aligned for type `T`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior. `T` must not contain padding bytes, as those are part of the checksum.
+    pub unsafe fn try_from_with_checksum(mut mmap_with_type: MmapMut) -> Result<Self> {
+        let (checksum, r#type) = mmap_to_checksummed_type_unbounded(&mut mmap_with_type)?;
+        let mmap = shared_mmap(mmap_with_type);
+        let mmap = Self {
+            r#type,
+            mmap,
+            checksum: Some(checksum),
+            zero_on_drop: false,
+            copy_on_write: false,
+            advice: None,
+            scrub: Mutex::new(None),
+        };
+
+        let stored = u32::from_le(*mmap.checksum.as_deref().unwrap());
+        let computed = mmap.compute_checksum();
+        if stored != computed {
+            return Err(Error::ChecksumMismatch(stored, computed));
+        }
+        Ok(mmap)
+    }
+
+    /// Write `value` into a mmap with a checksum header, to be reopened with
+    /// [`MmapType::try_from_with_checksum`].
+    ///
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior. `T` must not contain padding bytes, as those are part of the checksum.
+    pub unsafe fn new_with_checksum(mut mmap_with_type: MmapMut, value: T) -> Result<Self> {
+        let (checksum, r#type) = mmap_to_checksummed_type_unbounded(&mut mmap_with_type)?;
+        // Don't drop the previous data, it may not be a valid `T`
+        (r#type as *mut T).write(value);
+        let mut mmap = Self {
+            r#type,
+            mmap: shared_mmap(mmap_with_type),
+            checksum: Some(checksum),
+            zero_on_drop: false,
+            copy_on_write: false,
+            advice: None,
+            scrub: Mutex::new(None),
+        };
+        mmap.update_checksum();
+        Ok(mmap)
+    }
+
+    /// Whether the checksum header matches the current data
+    ///
+    /// Always `true` if this typed mmap has no checksum header.
+    pub fn verify_checksum(&self) -> bool {
+        match self.checksum.as_deref() {
+            Some(checksum) => u32::from_le(*checksum) == self.compute_checksum(),
+            None => true,
+        }
+    }
+
+    /// Incrementally verify the checksum header, digesting at most `budget` bytes per call
+    ///
+    /// Spreads the cost of [`MmapType::verify_checksum`] over multiple calls, so that a large mmap
+    /// can be scrubbed in the background without stalling on a single pass. Call repeatedly until
+    /// it no longer returns [`ScrubProgress::InProgress`], after which the next call starts a new
+    /// pass. The pass restarts if the checksum header is updated in between calls.
+    ///
+    /// A single CRC covers the whole type, so a mismatch can't tell which bytes are corrupted.
+    /// Data modified during a pass without updating the checksum is reported as corrupted.
+    pub fn scrub(&self, budget: usize) -> ScrubProgress {
+        let stored = match self.checksum.as_deref() {
+            Some(checksum) => u32::from_le(*checksum),
+            None => return ScrubProgress::NoChecksum,
+        };
+
+        let bytes = self.as_bytes();
+        let mut scrub = self.scrub.lock();
+        if !matches!(&*scrub, Some(state) if state.expected == stored) {
+            *scrub = Some(ScrubState {
+                expected: stored,
+                offset: 0,
+                digest: CRC32C.digest(),
+            });
+        }
+        let state = scrub.as_mut().unwrap();
+
+        let end = bytes.len().min(state.offset.saturating_add(budget));
+        state.digest.update(&bytes[state.offset..end]);
+        state.offset = end;
+        if end < bytes.len() {
+            return ScrubProgress::InProgress {
+                offset: end,
+                len: bytes.len(),
+            };
+        }
+
+        let computed = scrub.take().unwrap().digest.finalize();
+        if computed == stored {
+            ScrubProgress::Verified
+        } else {
+            ScrubProgress::Corrupted { stored, computed }
+        }
+    }
+
+    /// Update the checksum header to match the current data
+    ///
+    /// Must be called after modifying the data, before obtaining a [`MmapType::flusher`], so that
+    /// the flushed header matches the flushed data. Does nothing if this typed mmap has no
+    /// checksum header.
+    pub fn update_checksum(&mut self) {
+        let computed = self.compute_checksum();
+        if let Some(checksum) = self.checksum.as_deref_mut() {
+            *checksum = computed.to_le();
+        }
+    }
+
+    /// Raw bytes of type `T`
+    ///
+    /// Padding bytes in `T` hold whatever is stored in the mmap.
+    pub fn as_bytes(&self) -> &[u8] {
+        let ptr = self.r#type as *const T as *const u8;
+        // Safety: `T` is fully backed by initialized mmap data
+        unsafe { slice::from_raw_parts(ptr, mem::size_of::<T>()) }
+    }
+
+    /// Mutable raw bytes of type `T`
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because writing arbitrary bytes may break type `T` resulting in undefined behavior.
+    pub unsafe fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let ptr = self.r#type as *mut T as *mut u8;
+        slice::from_raw_parts_mut(ptr, mem::size_of::<T>())
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        CRC32C.checksum(self.as_bytes())
+    }
+}
+
+impl<T> MmapType<T>
+where
+    T: Pod,
+{
+    /// Transform a mmap into a typed mmap of [`Pod`] type `T`.
+    ///
+    /// This is safe, because any data in the mmap is valid for a [`Pod`] type.
     ///
     /// # Panics
     ///
+    /// - panics when the size of the mmap doesn't match size `T`
     /// - panics when the mmap data is not correctly aligned for type `T`
-    /// - See: [`mmap_to_type_unbounded`]
-    pub unsafe fn try_from(mut mmap_with_type: MmapMut) -> Result<Self> {
-        let r#type = mmap_to_type_unbounded(&mut mmap_with_type)?;
-        let mmap = Arc::new(mmap_with_type);
-        Ok(Self { r#type, mmap })
+    /// - See: [`MmapType::try_from_pod`]
+    pub fn from_pod(mmap_with_type: MmapMut) -> Self {
+        Self::try_from_pod(mmap_with_type).unwrap()
+    }
+
+    /// Transform a mmap into a typed mmap of [`Pod`] type `T`.
+    ///
+    /// This is safe, because any data in the mmap is valid for a [`Pod`] type.
+    ///
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
+    pub fn try_from_pod(mmap_with_type: MmapMut) -> Result<Self> {
+        unsafe { Self::try_from(mmap_with_type) }
     }
 }
 
@@ -135,14 +431,15 @@ where
     ///
     /// - panics when the size of the mmap isn't a multiple of size `T`
     /// - panics when the mmap data is not correctly aligned for type `T`
-    /// - See: [`mmap_to_slice_unbounded`]
+    /// - See: [`MmapType::try_slice_from`]
     pub unsafe fn slice_from(mmap_with_slice: MmapMut) -> Self {
         Self::try_slice_from(mmap_with_slice).unwrap()
     }
 
     /// Transform a mmap into a typed slice mmap of type `&[T]`.
     ///
-    /// Returns an error when the mmap has an incorrect size.
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
     ///
     /// # Warning
     ///
@@ -154,15 +451,67 @@ where
     ///
     /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
     /// behavior.
-    ///
-    /// # Panics
-    ///
-    /// - panics when the mmap data is not correctly aligned for type `T`
-    /// - See: [`mmap_to_slice_unbounded`]
     pub unsafe fn try_slice_from(mut mmap_with_slice: MmapMut) -> Result<Self> {
         let r#type = mmap_to_slice_unbounded(&mut mmap_with_slice, 0)?;
-        let mmap = Arc::new(mmap_with_slice);
-        Ok(Self { r#type, mmap })
+        let mmap = shared_mmap(mmap_with_slice);
+        Ok(Self {
+            r#type,
+            mmap,
+            checksum: None,
+            zero_on_drop: false,
+            copy_on_write: false,
+            advice: None,
+            scrub: Mutex::new(None),
+        })
+    }
+
+    /// Number of elements in the slice
+    pub fn capacity(&self) -> usize {
+        self.r#type.len()
+    }
+
+    /// Get the element at `index`, or `None` if out of bounds
+    pub fn get_checked(&self, index: usize) -> Option<&T> {
+        self.r#type.get(index)
+    }
+
+    /// Get the element at `index`
+    ///
+    /// Returns an error holding the index and length if out of bounds.
+    pub fn try_get(&self, index: usize) -> Result<&T> {
+        let len = self.r#type.len();
+        self.r#type
+            .get(index)
+            .ok_or(Error::OutOfBounds { index, len })
+    }
+
+    /// Get the mutable element at `index`
+    ///
+    /// Returns an error holding the index and length if out of bounds.
+    pub fn try_get_mut(&mut self, index: usize) -> Result<&mut T> {
+        let len = self.r#type.len();
+        self.r#type
+            .get_mut(index)
+            .ok_or(Error::OutOfBounds { index, len })
+    }
+
+    /// Raw bytes of the slice of type `T`, of the slice length times size `T`
+    ///
+    /// Padding bytes in `T` hold whatever is stored in the mmap.
+    pub fn as_bytes(&self) -> &[u8] {
+        let ptr = self.r#type.as_ptr() as *const u8;
+        // Safety: the slice is fully backed by initialized mmap data
+        unsafe { slice::from_raw_parts(ptr, mem::size_of_val(self.r#type)) }
+    }
+
+    /// Mutable raw bytes of the slice of type `T`, of the slice length times size `T`
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because writing arbitrary bytes may break type `T` resulting in undefined behavior.
+    pub unsafe fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let len = mem::size_of_val(self.r#type);
+        slice::from_raw_parts_mut(self.r#type.as_mut_ptr() as *mut u8, len)
     }
 }
 
@@ -171,17 +520,196 @@ where
     T: ?Sized + 'static,
 {
     /// Get flusher to explicitly flush mmap at a later time
+    ///
+    /// The flusher does nothing if this type is dropped in the meantime, because dropping already
+    /// flushes the mmap. It also does nothing for a copy-on-write mapping, see
+    /// [`MmapType::from_cow`].
     pub fn flusher(&self) -> Flusher {
-        // TODO: if we explicitly flush when dropping this type, we can switch to a weak reference
-        // here to only flush if it hasn't been done already
+        if self.copy_on_write {
+            return Box::new(|| Ok(()));
+        }
+
+        Box::new({
+            let mmap = Arc::downgrade(&self.mmap);
+            move || {
+                if let Some(mmap) = mmap.upgrade() {
+                    mmap.flush()?;
+                }
+                Ok(())
+            }
+        })
+    }
+
+    /// Get flusher to explicitly flush mmap asynchronously at a later time
+    ///
+    /// See [`MmapType::flush_async`] for the durability implications. Like
+    /// [`MmapType::flusher`], this does nothing if this type is dropped in the meantime, or for a
+    /// copy-on-write mapping.
+    pub fn async_flusher(&self) -> Flusher {
+        if self.copy_on_write {
+            return Box::new(|| Ok(()));
+        }
+
         Box::new({
-            let mmap = self.mmap.clone();
+            let mmap = Arc::downgrade(&self.mmap);
             move || {
-                mmap.flush()?;
+                if let Some(mmap) = mmap.upgrade() {
+                    mmap.flush_async()?;
+                }
                 Ok(())
             }
         })
     }
+
+    /// Size in bytes of the backing mmap, including any header
+    pub fn byte_len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// Flush and downgrade to a read-only typed mmap, for data that is no longer modified
+    ///
+    /// The mapping is made read-only in place, so the data keeps its address. Flushers obtained
+    /// before do nothing afterwards.
+    ///
+    /// Returns an error for a mmap that is zeroed on drop, because the read-only mmap cannot zero
+    /// its data. Also returns an error if a flusher is running at the same time.
+    pub fn into_readonly(self) -> io::Result<MmapTypeReadOnly<T>> {
+        if self.zero_on_drop {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Cannot downgrade mmap that is zeroed on drop to read-only",
+            ));
+        }
+        if !self.copy_on_write {
+            self.mmap.flush()?;
+        }
+
+        // Take apart without dropping, which would flush again
+        let this = mem::ManuallyDrop::new(self);
+        // Safety: the reference stays valid as long as the mmap, which is moved into the read-only
+        // type below, and is never used mutably again
+        let r#type: &'static T = unsafe { &*(&*this.r#type as *const T) };
+        let mmap = unsafe { std::ptr::read(&this.mmap) };
+
+        let mmap = Arc::try_unwrap(mmap).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Cannot downgrade mmap to read-only while it is being flushed",
+            )
+        })?;
+        Ok(MmapTypeReadOnly {
+            r#type,
+            mmap: mmap.make_read_only()?,
+        })
+    }
+
+    /// Schedule all modified pages of the mmap for writeback, without waiting for it
+    ///
+    /// This returns immediately, and does not guarantee the data is persisted on return. Data may
+    /// still be lost on a crash or power loss afterwards. Use [`MmapType::flusher`] where
+    /// durability is required.
+    pub fn flush_async(&self) -> OperationResult<()> {
+        self.mmap.flush_async()?;
+        Ok(())
+    }
+
+    /// Flush `len` bytes of the mmap, starting at byte `offset`
+    ///
+    /// The range is relative to the start of the mmap, including any header. Returns an error if
+    /// the range exceeds the mmap.
+    pub fn flush_range(&self, offset: usize, len: usize) -> OperationResult<()> {
+        check_range(offset, len, self.mmap.len())?;
+        self.mmap.flush_range(offset, len)?;
+        Ok(())
+    }
+
+    /// Lock memory mapped pages in memory
+    ///
+    /// Faults in all pages of the mmap and prevents them from being paged out, see `mlock(2)`
+    /// and `VirtualLock` on Windows. Returns an [`io::ErrorKind::Unsupported`] error on other
+    /// platforms.
+    pub fn lock(&self) -> io::Result<()> {
+        lock_pages(&self.mmap)
+    }
+
+    /// Prefetch memory mapped pages and lock them in memory, to keep a hot mmap resident
+    ///
+    /// Advises [`Advice::WillNeed`] so pages are read ahead of time, then locks them, see
+    /// [`MmapType::lock`]. Returns the first error of either.
+    pub fn prefetch_and_lock(&self) -> io::Result<()> {
+        self.mmap.madvise(Advice::WillNeed)?;
+        self.lock()
+    }
+
+    /// Unlock memory mapped pages previously locked with [`MmapType::lock`]
+    ///
+    /// This does not evict pages from memory, it only allows the kernel to reclaim them again,
+    /// see `munlock(2)` and `VirtualUnlock` on Windows. Returns an [`io::ErrorKind::Unsupported`]
+    /// error on other platforms.
+    pub fn unlock(&self) -> io::Result<()> {
+        unlock_pages(&self.mmap)
+    }
+
+    /// Advise OS how the mmap will be accessed. On non-Unix platforms this is a no-op.
+    pub fn madvise(&self, advice: Advice) -> io::Result<()> {
+        self.mmap.madvise(advice)
+    }
+
+    /// Advise OS how the mmap will be accessed, including advice that may discard data, such as
+    /// [`Advice::DontNeed`]. See [`Madviseable::madvise_discard_range`].
+    ///
+    /// Returns an error for advice that may discard data of a copy-on-write mapping, see
+    /// [`MmapType::from_cow`], as its changes only live in memory. [`Advice::Free`] only applies
+    /// to private anonymous mappings, the OS rejects it for shared file mappings.
+    ///
+    /// # Safety
+    ///
+    /// The mmap must be a shared file mapping, of which discarded pages are read back from the
+    /// file. Otherwise the caller must accept losing data, see
+    /// [`Madviseable::madvise_discard_range`].
+    pub unsafe fn madvise_discard(&self, advice: Advice) -> io::Result<()> {
+        if self.copy_on_write && advice.discards_data() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{advice:?} advice would discard the changes of a copy-on-write mmap"),
+            ));
+        }
+        self.mmap.madvise_discard_range(0, self.mmap.len(), advice)
+    }
+
+    /// Advise OS how the mmap will be accessed, and remember the advice for this mmap
+    ///
+    /// The advice is applied again when the mmap is remapped, see [`MmapSlice::resize`]. Changes
+    /// of the global advice don't affect this mmap anymore.
+    pub fn set_advice(&mut self, advice: Advice) -> io::Result<()> {
+        self.mmap.madvise(advice)?;
+        self.advice = Some(advice);
+        madvise::unregister(&self.mmap);
+        Ok(())
+    }
+
+    /// Advice of this mmap, or the global advice if none is set, see [`madvise::get_global`]
+    pub fn advice(&self) -> Advice {
+        self.advice.unwrap_or_else(madvise::get_global)
+    }
+
+    /// Populate the page cache by touching every page of the mmap
+    ///
+    /// Faults in pages ahead of time, to prevent faulting them in one by one on first access.
+    /// This is cheap if all pages are already resident. Mmaps larger than [`MAX_POPULATE_SIZE`]
+    /// are not populated.
+    pub fn populate(&self) {
+        populate(&self.mmap);
+    }
+
+    /// Number of bytes of the mmap resident in memory
+    ///
+    /// Counts resident pages with `mincore(2)`, which may include the header and the remainder of
+    /// the last page. Returns an [`io::ErrorKind::Unsupported`] error on platforms other than
+    /// Linux.
+    pub fn resident_bytes(&self) -> io::Result<usize> {
+        resident_bytes(&self.mmap)
+    }
 }
 
 impl<T> Deref for MmapType<T>
@@ -210,11 +738,43 @@ where
     }
 }
 
+impl<T> Drop for MmapType<T>
+where
+    T: ?Sized + 'static,
+{
+    /// Flush the mmap, so no changes are lost if no flusher is called afterwards
+    ///
+    /// [`MmapSlice`] and [`MmapBitSlice`] wrap this type, and are flushed here exactly once.
+    ///
+    /// If constructed with [`MmapType::try_from_zero_on_drop`], the data is overwritten with zeros
+    /// first.
+    fn drop(&mut self) {
+        if self.zero_on_drop {
+            let len = mem::size_of_val(self.r#type);
+            // Safety: we still hold the unique mutable reference to the data, which is never used
+            // again
+            unsafe { std::ptr::write_bytes(self.r#type as *mut T as *mut u8, 0, len) };
+        }
+
+        if self.copy_on_write {
+            return;
+        }
+        if let Err(err) = self.mmap.flush() {
+            log::error!("Failed to flush mmap on drop: {err}");
+        }
+    }
+}
+
 /// Slice of type `T` on a memory mapped file
 ///
 /// Functions as if it is `&[T]` because this implements [`Deref`] and [`DerefMut`].
 ///
 /// A helper because [`MmapType`] doesn't support slices directly.
+///
+/// For [`Pod`] types, prefer the [`TryFrom<MmapMut>`] implementation: it checks size and
+/// alignment and requires no `unsafe`, because any data is valid for them. Use the `unsafe`
+/// [`MmapSlice::from`] and [`MmapSlice::try_from_unchecked`] only for other types, such as
+/// `repr(C)` structs that aren't [`Pod`], for which the caller must guarantee the data is valid.
 pub struct MmapSlice<T>
 where
     T: Sized + 'static,
@@ -236,395 +796,2755 @@ impl<T> MmapSlice<T> {
     ///
     /// - panics when the size of the mmap isn't a multiple of size `T`
     /// - panics when the mmap data is not correctly aligned for type `T`
-    /// - See: [`mmap_to_slice_unbounded`]
+    /// - See: [`MmapSlice::try_from_unchecked`]
     pub unsafe fn from(mmap_with_slice: MmapMut) -> Self {
-        Self::try_from(mmap_with_slice).unwrap()
+        Self::try_from_unchecked(mmap_with_slice).unwrap()
     }
 
     /// Transform a mmap into a typed slice mmap of type `&[T]`.
     ///
     /// This method is specifically intended for slices.
     ///
-    /// Returns an error when the mmap has an incorrect size.
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
     ///
     /// # Safety
     ///
     /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
     /// behavior.
-    ///
-    /// # Panics
-    ///
-    /// - panics when the mmap data is not correctly aligned for type `T`
-    /// - See: [`mmap_to_slice_unbounded`]
-    pub unsafe fn try_from(mmap_with_slice: MmapMut) -> Result<Self> {
+    pub unsafe fn try_from_unchecked(mmap_with_slice: MmapMut) -> Result<Self> {
         MmapType::try_slice_from(mmap_with_slice).map(|mmap| Self { mmap })
     }
 
-    /// Get flusher to explicitly flush mmap at a later time
-    pub fn flusher(&self) -> Flusher {
-        self.mmap.flusher()
+    /// Transform a mmap into a typed slice mmap of type `&[T]`, advising the OS how it will be
+    /// accessed.
+    ///
+    /// The `advice` replaces the global advice applied when the mmap was opened, see
+    /// [`crate::madvise::get_global`]. It is remembered for this mmap, see
+    /// [`MmapType::set_advice`].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn try_from_with_advice(
+        mmap_with_slice: MmapMut,
+        advice: Advice,
+    ) -> OperationResult<Self> {
+        let mut mmap = Self::try_from_unchecked(mmap_with_slice)?;
+        mmap.set_advice(advice)?;
+        Ok(mmap)
     }
-}
-
-impl<T> Deref for MmapSlice<T> {
-    type Target = MmapType<[T]>;
 
-    fn deref(&self) -> &Self::Target {
-        &self.mmap
+    /// Create a zeroed file at `path` sized to fit `len` elements, and map it as slice `&[T]`.
+    ///
+    /// Replaces any existing file at `path`. The mmap is advised with the global advice, see
+    /// [`MmapSlice::create_with_advice`] to use another.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because zeroed data may break type `T` resulting in undefined behavior.
+    pub unsafe fn create(path: &Path, len: usize) -> OperationResult<Self> {
+        let mmap = mmap_ops::create_zeroed_mmap(path, len * mem::size_of::<T>())?;
+        Ok(Self::try_from_unchecked(mmap)?)
     }
-}
 
-impl<T> DerefMut for MmapSlice<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.mmap
+    /// Create a zeroed file at `path` sized to fit `len` elements, and map it as slice `&[T]`,
+    /// advising the OS how it will be accessed.
+    ///
+    /// See [`MmapSlice::create`] and [`MmapSlice::try_from_with_advice`].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because zeroed data may break type `T` resulting in undefined behavior.
+    pub unsafe fn create_with_advice(
+        path: &Path,
+        len: usize,
+        advice: Advice,
+    ) -> OperationResult<Self> {
+        let mmap = mmap_ops::create_zeroed_mmap(path, len * mem::size_of::<T>())?;
+        Self::try_from_with_advice(mmap, advice)
     }
-}
-
-/// [`BitSlice`] on a memory mapped file
-///
-/// Functions as if it is a [`BitSlice`] because this implements [`Deref`] and [`DerefMut`].
-pub struct MmapBitSlice {
-    mmap: MmapType<BitSlice>,
-}
 
-impl MmapBitSlice {
-    /// Transform a mmap into a [`BitSlice`].
+    /// Map `len` elements of the file at `path`, starting at byte `offset`, as slice `&[T]`.
     ///
-    /// A (non-zero) header size in bytes may be provided to omit from the BitSlice data.
+    /// This maps a window of the file rather than the whole file, for files packing multiple
+    /// arrays back to back. The `offset` must be a multiple of the page size on Unix, and of the
+    /// allocation granularity on Windows, see [`mapping_granularity`].
     ///
-    /// # Panics
+    /// Returns an error when the offset is misaligned, or when the range exceeds the file.
     ///
-    /// - panics when the size of the mmap isn't a multiple of the inner [`BitSlice`] type
-    /// - panics when the mmap data is not correctly aligned to the inner [`BitSlice`] type
-    /// - panics when the header size isn't a multiple of the inner [`BitSlice`] type
-    /// - See: [`mmap_to_slice_unbounded`]
-    pub fn from(mmap: MmapMut, header_size: usize) -> Self {
-        Self::try_from(mmap, header_size).unwrap()
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn open_range(path: &Path, offset: usize, len: usize) -> OperationResult<Self> {
+        let granularity = mapping_granularity();
+        if offset % granularity != 0 {
+            return Err(Error::OffsetAlignment(granularity, offset).into());
+        }
+
+        let size = len * mem::size_of::<T>();
+        let file_len = path.metadata()?.len() as usize;
+        check_range(offset, size, file_len)?;
+
+        let mmap = mmap_ops::open_write_mmap_range(path, offset, size)?;
+        Ok(Self::try_from_unchecked(mmap)?)
     }
 
-    /// Transform a mmap into a [`BitSlice`].
+    /// Grow the slice to `new_len` elements by extending the file at `path` and remapping it.
     ///
-    /// Returns an error when the mmap has an incorrect size.
+    /// `path` must be the file this slice is mapped from. Existing elements are preserved, new
+    /// elements are zeroed.
     ///
-    /// A (non-zero) header size in bytes may be provided to omit from the BitSlice data.
+    /// The slice reference is derived from the new mmap and replaces the current one in a single
+    /// assignment, which also releases the current mmap. The current reference is therefore never
+    /// used after remapping, and never outlives its mmap. The current mmap is flushed before it is
+    /// released. Flushers obtained before resizing do not keep it alive, and do nothing afterwards,
+    /// so get a new flusher after resizing.
     ///
-    /// # Panics
+    /// Advice set for this mmap is applied to the new mmap, see [`MmapType::set_advice`].
+    /// Otherwise the new mmap is advised with the global advice.
     ///
-    /// - panics when the mmap data is not correctly aligned to the inner [`BitSlice`] type
-    /// - panics when the header size isn't a multiple of the inner [`BitSlice`] type
-    /// - See: [`mmap_to_slice_unbounded`]
-    pub fn try_from(mut mmap: MmapMut, header_size: usize) -> Result<Self> {
-        let data = unsafe { mmap_to_slice_unbounded(&mut mmap, header_size)? };
-        let bitslice = BitSlice::from_slice_mut(data);
-        let mmap = Arc::new(mmap);
+    /// # Safety
+    ///
+    /// Unsafe because zeroed or malformed data in the mmap may break type `T` resulting in
+    /// undefined behavior.
+    pub unsafe fn resize(&mut self, path: &Path, new_len: usize) -> OperationResult<()> {
+        if new_len < self.len() {
+            return Err(OperationError::service_error(format!(
+                "Cannot shrink mmap slice from {} to {new_len} elements",
+                self.len(),
+            )));
+        }
+
+        self.flusher()()?;
+        mmap_ops::create_and_ensure_length(path, new_len * mem::size_of::<T>())?;
+        let advice = self.mmap.advice;
+        self.remap(path, advice)
+    }
+
+    /// Shrink the slice to `new_len` elements by truncating the file at `path` and remapping it.
+    ///
+    /// `path` must be the file this slice is mapped from. The first `new_len` elements are
+    /// preserved, and the space of the remaining elements is released.
+    ///
+    /// The slice is flushed, and the current mmap is released before truncating the file, so the
+    /// current reference never refers to truncated data. On error the slice may be left empty.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn truncate(&mut self, path: &Path, new_len: usize) -> OperationResult<()> {
+        if new_len > self.len() {
+            return Err(OperationError::service_error(format!(
+                "Cannot truncate mmap slice from {} to {new_len} elements",
+                self.len(),
+            )));
+        }
+
+        self.flusher()()?;
+        let advice = self.mmap.advice;
+        self.mmap = MmapType::try_slice_from(MmapMut::map_anon(0)?)?;
+
+        mmap_ops::create_and_ensure_length(path, new_len * mem::size_of::<T>())?;
+        self.remap(path, advice)
+    }
+
+    /// Map the file at `path` again, and apply `advice` set for this mmap, if any.
+    unsafe fn remap(&mut self, path: &Path, advice: Option<Advice>) -> OperationResult<()> {
+        let mmap = mmap_ops::open_write_mmap(path)?;
+        self.mmap = MmapType::try_slice_from(mmap)?;
+        if let Some(advice) = advice {
+            self.mmap.set_advice(advice)?;
+        }
+        Ok(())
+    }
+
+    /// Get flusher to explicitly flush mmap at a later time
+    pub fn flusher(&self) -> Flusher {
+        self.mmap.flusher()
+    }
+
+    /// Flush `len` elements of the slice, starting at element `offset`
+    ///
+    /// Returns an error if the range exceeds the slice.
+    pub fn flush_range(&self, offset: usize, len: usize) -> OperationResult<()> {
+        check_range(offset, len, self.len())?;
+        let size_t = mem::size_of::<T>();
+        self.mmap.flush_range(offset * size_t, len * size_t)
+    }
+}
+
+impl<T> MmapSlice<T>
+where
+    T: Pod,
+{
+    /// Transform a mmap into a typed slice mmap of [`Pod`] type `&[T]`.
+    ///
+    /// This is safe, because any data in the mmap is valid for a [`Pod`] type.
+    ///
+    /// # Panics
+    ///
+    /// - panics when the size of the mmap isn't a multiple of size `T`
+    /// - panics when the mmap data is not correctly aligned for type `T`
+    /// - See: [`MmapSlice::try_from_pod`]
+    pub fn from_pod(mmap_with_slice: MmapMut) -> Self {
+        Self::try_from_pod(mmap_with_slice).unwrap()
+    }
+
+    /// Transform a mmap into a typed slice mmap of [`Pod`] type `&[T]`.
+    ///
+    /// This is safe, because any data in the mmap is valid for a [`Pod`] type.
+    ///
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
+    pub fn try_from_pod(mmap_with_slice: MmapMut) -> Result<Self> {
+        unsafe { Self::try_from_unchecked(mmap_with_slice) }
+    }
+}
+
+impl<T> TryFrom<MmapMut> for MmapSlice<T>
+where
+    T: Pod,
+{
+    type Error = Error;
+
+    /// Transform a mmap into a typed slice mmap of [`Pod`] type `&[T]`.
+    ///
+    /// See [`MmapSlice::try_from_pod`].
+    fn try_from(mmap_with_slice: MmapMut) -> Result<Self> {
+        Self::try_from_pod(mmap_with_slice)
+    }
+}
+
+impl<T> MmapSlice<T>
+where
+    T: Copy,
+{
+    /// Set all elements to `value`, for example to initialize a newly created slice
+    pub fn fill(&mut self, value: T) {
+        self.mmap.deref_mut().fill(value);
+    }
+}
+
+impl<T> MmapSlice<T>
+where
+    T: Float,
+{
+    /// Check all values are finite, so there are no NaN or infinite values
+    ///
+    /// Returns the index of the first value that is not finite. This scans the whole slice, and
+    /// is not done when opening. Use it to fail fast when loading data of which integrity matters.
+    pub fn validate_finite(&self) -> std::result::Result<(), usize> {
+        match first_non_finite(self) {
+            Some(index) => Err(index),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> Deref for MmapSlice<T> {
+    type Target = MmapType<[T]>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mmap
+    }
+}
+
+impl<T> DerefMut for MmapSlice<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.mmap
+    }
+}
+
+/// [`BitSlice`] on a memory mapped file
+///
+/// Functions as if it is a [`BitSlice`] because this implements [`Deref`] and [`DerefMut`].
+///
+/// Keeps a cached count of set bits, which is kept up to date by [`MmapBitSlice::set`],
+/// [`MmapBitSlice::set_range`] and [`MmapBitSlice::set_atomic`]. Modifying bits through
+/// [`DerefMut`] does not update it, see [`MmapBitSlice::recompute_count`].
+pub struct MmapBitSlice {
+    /// Owns the mmap
+    ///
+    /// The bits are never accessed through the reference in `MmapType::r#type`, because
+    /// [`MmapBitSlice::set_atomic`] writes through the mmap pointer, which would invalidate it.
+    /// Every access derives a new reference from the mmap pointer instead, see
+    /// [`MmapBitSlice::words_ptr`].
+    mmap: MmapType<BitSlice>,
+    /// Size in bytes of the header preceding the [`BitSlice`] data in the mmap
+    header_size: usize,
+    /// Cached number of set bits
+    count: AtomicUsize,
+}
+
+impl MmapBitSlice {
+    /// Transform a mmap into a [`BitSlice`].
+    ///
+    /// A (non-zero) header size in bytes may be provided to omit from the BitSlice data.
+    ///
+    /// # Panics
+    ///
+    /// - panics when the size of the mmap isn't a multiple of the inner [`BitSlice`] type
+    /// - panics when the mmap data is not correctly aligned to the inner [`BitSlice`] type
+    /// - panics when the header size isn't a multiple of the inner [`BitSlice`] type
+    /// - See: [`MmapBitSlice::try_from`]
+    pub fn from(mmap: MmapMut, header_size: usize) -> Self {
+        Self::try_from(mmap, header_size).unwrap()
+    }
+
+    /// Transform a mmap into a [`BitSlice`].
+    ///
+    /// Returns an error when the mmap has an incorrect size, when the mmap data is not correctly
+    /// aligned to the inner [`BitSlice`] type, or when the header size isn't a multiple of the
+    /// inner [`BitSlice`] type.
+    ///
+    /// A (non-zero) header size in bytes may be provided to omit from the BitSlice data.
+    pub fn try_from(mut mmap: MmapMut, header_size: usize) -> Result<Self> {
+        let data = unsafe { mmap_to_slice_unbounded(&mut mmap, header_size)? };
+        let bitslice = BitSlice::from_slice_mut(data);
+        let mmap = shared_mmap(mmap);
+        let count = popcount(bitslice);
 
         Ok(Self {
             mmap: MmapType {
                 r#type: bitslice,
                 mmap,
+                checksum: None,
+                zero_on_drop: false,
+                copy_on_write: false,
+                advice: None,
+                scrub: Mutex::new(None),
             },
+            header_size,
+            count: AtomicUsize::new(count),
         })
     }
 
+    /// Create a zeroed file at `path` with a header of `header_size` bytes followed by at least
+    /// `bits` bits, and map it as [`BitSlice`].
+    ///
+    /// The number of bits is rounded up to fill the inner [`BitSlice`] elements. Replaces any
+    /// existing file at `path`.
+    pub fn create(path: &Path, bits: usize, header_size: usize) -> OperationResult<Self> {
+        let len = header_size + div_ceil(bits, usize::BITS as usize) * mem::size_of::<usize>();
+        let mmap = mmap_ops::create_zeroed_mmap(path, len)?;
+        Ok(Self::try_from(mmap, header_size)?)
+    }
+
+    /// Set the bit at `index` to `value`, keeping the cached count of set bits up to date
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: bool) {
+        let previous = self.bits_mut().replace(index, value);
+        if previous != value {
+            if value {
+                *self.count.get_mut() += 1;
+            } else {
+                *self.count.get_mut() -= 1;
+            }
+        }
+    }
+
+    /// Set all bits in `range` to `value`, keeping the cached count of set bits up to date
+    ///
+    /// Sets whole inner [`BitSlice`] elements at once, and masks the partial elements at the
+    /// start and end of the range. Much faster than calling [`MmapBitSlice::set`] for every bit.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `range` is out of bounds.
+    pub fn set_range(&mut self, range: Range<usize>, value: bool) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "range {range:?} out of bounds for bit slice of length {}",
+            self.len(),
+        );
+        if range.is_empty() {
+            return;
+        }
+
+        let bits = usize::BITS as usize;
+        let first = range.start / bits;
+        let last = (range.end - 1) / bits;
+        let mut count = *self.count.get_mut();
+        let words = &mut self.bits_mut().as_raw_slice_mut()[first..=last];
+        for (index, word) in (first..).zip(words) {
+            let mut mask = usize::MAX;
+            if index == first {
+                mask &= usize::MAX << (range.start % bits);
+            }
+            if index == last {
+                mask &= usize::MAX >> ((last + 1) * bits - range.end);
+            }
+
+            let previous = (*word & mask).count_ones() as usize;
+            if value {
+                *word |= mask;
+                count += mask.count_ones() as usize - previous;
+            } else {
+                *word &= !mask;
+                count -= previous;
+            }
+        }
+        *self.count.get_mut() = count;
+    }
+
+    /// Atomically set the bit at `index` to `value`, without requiring exclusive access
+    ///
+    /// Allows setting bits from multiple threads concurrently, using an atomic bitwise OR or AND
+    /// on the inner [`BitSlice`] element holding the bit. Only that element is updated atomically,
+    /// and only with [`Ordering::Relaxed`]. It therefore implies no ordering with other memory
+    /// operations, which must be synchronized separately if they depend on this bit. Use
+    /// [`MmapBitSlice::get_atomic`] to read bits while they may be set concurrently.
+    ///
+    /// Keeps the cached count of set bits up to date.
+    ///
+    /// # Safety
+    ///
+    /// No reference to the bits may be alive while this is called, on any thread. That is any
+    /// borrow through [`Deref`], or by a method reading the bits such as
+    /// [`MmapBitSlice::iter_ones`], [`MmapBitSlice::first_one`] or [`MmapBitSlice::populate`].
+    /// Only [`MmapBitSlice::set_atomic`] and [`MmapBitSlice::get_atomic`] may overlap with it.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    pub unsafe fn set_atomic(&self, index: usize, value: bool) {
+        let mask = 1 << (index % usize::BITS as usize);
+        let word = self.atomic_word(index);
+
+        if value {
+            let previous = word.fetch_or(mask, Ordering::Relaxed);
+            if previous & mask == 0 {
+                self.count.fetch_add(1, Ordering::Relaxed);
+            }
+        } else {
+            let previous = word.fetch_and(!mask, Ordering::Relaxed);
+            if previous & mask != 0 {
+                self.count.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Atomically get the bit at `index`
+    ///
+    /// Unlike reading through [`Deref`], this may overlap with [`MmapBitSlice::set_atomic`] on
+    /// other threads. Loads with [`Ordering::Relaxed`], see [`MmapBitSlice::set_atomic`].
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    pub fn get_atomic(&self, index: usize) -> bool {
+        let mask = 1 << (index % usize::BITS as usize);
+        self.atomic_word(index).load(Ordering::Relaxed) & mask != 0
+    }
+
+    /// Inner [`BitSlice`] element holding the bit at `index`, for atomic access
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    fn atomic_word(&self, index: usize) -> &AtomicUsize {
+        // Don't use `len()`, it creates a reference to the bits through `Deref`
+        let len = self.words_len() * usize::BITS as usize;
+        assert!(
+            index < len,
+            "index {index} out of bounds for bit slice of length {len}",
+        );
+        // Safety: the pointer is in bounds and derived from the mmap pointer, not from a
+        // reference to the bits, see `words_ptr`. `AtomicUsize` has the same size and bit
+        // validity as `usize`, and the inner elements are aligned to `usize` which equals the
+        // alignment of `AtomicUsize`. Writes through the returned `AtomicUsize` don't conflict
+        // with non-atomic references to the bits, because the caller of `set_atomic` guarantees
+        // none is alive.
+        unsafe { &*(self.words_ptr().add(index / usize::BITS as usize) as *const AtomicUsize) }
+    }
+
+    /// Pointer to the inner [`BitSlice`] elements, after the header
+    ///
+    /// Derived from the pointer of the mmap itself, which is a raw pointer returned by the OS
+    /// and is not a Rust allocation. Creating a reference from it, rather than using
+    /// `MmapType::r#type`, keeps references to the bits valid after [`MmapBitSlice::set_atomic`]
+    /// wrote through this pointer.
+    fn words_ptr(&self) -> *mut usize {
+        // Safety: the header size is within the mmap, checked when mapping the bits
+        unsafe { self.mmap.mmap.as_ptr().add(self.header_size) as *mut usize }
+    }
+
+    /// Number of inner [`BitSlice`] elements, after the header
+    fn words_len(&self) -> usize {
+        (self.mmap.mmap.len() - self.header_size) / mem::size_of::<usize>()
+    }
+
+    fn bits(&self) -> &BitSlice {
+        // Safety: the elements are in bounds, aligned and initialized, checked when mapping the
+        // bits. The reference borrows `self`, so it can't outlive the mmap.
+        let words = unsafe { slice::from_raw_parts(self.words_ptr(), self.words_len()) };
+        BitSlice::from_slice(words)
+    }
+
+    fn bits_mut(&mut self) -> &mut BitSlice {
+        // Safety: see `bits`, and the reference mutably borrows `self`, so no other reference to
+        // the bits is alive
+        let words = unsafe { slice::from_raw_parts_mut(self.words_ptr(), self.words_len()) };
+        BitSlice::from_slice_mut(words)
+    }
+
+    /// Recompute the cached count of set bits
+    ///
+    /// Must be called after modifying bits through [`DerefMut`], rather than through
+    /// [`MmapBitSlice::set`].
+    pub fn recompute_count(&mut self) {
+        let count = popcount(self.bits());
+        *self.count.get_mut() = count;
+    }
+
     /// Get flusher to explicitly flush mmap at a later time
     pub fn flusher(&self) -> Flusher {
         self.mmap.flusher()
     }
+
+    /// Iterate over the indices of all set bits, in ascending order
+    ///
+    /// Scans the inner [`BitSlice`] elements a word at a time, skipping over unset words.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        let bits = usize::BITS as usize;
+        self.bits()
+            .as_raw_slice()
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, &word)| {
+                let mut word = word;
+                iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    // Clear lowest set bit
+                    word &= word - 1;
+                    Some(index * bits + bit)
+                })
+            })
+    }
+
+    /// Index of the first set bit, scanning the inner [`BitSlice`] elements a word at a time
+    pub fn first_one(&self) -> Option<usize> {
+        let bits = usize::BITS as usize;
+        self.bits()
+            .as_raw_slice()
+            .iter()
+            .enumerate()
+            .find(|(_, &word)| word != 0)
+            .map(|(index, word)| index * bits + word.trailing_zeros() as usize)
+    }
+
+    /// Index of the first unset bit, scanning the inner [`BitSlice`] elements a word at a time
+    pub fn first_zero(&self) -> Option<usize> {
+        let bits = usize::BITS as usize;
+        self.bits()
+            .as_raw_slice()
+            .iter()
+            .enumerate()
+            .find(|(_, &word)| word != usize::MAX)
+            .map(|(index, word)| index * bits + word.trailing_ones() as usize)
+    }
+
+    /// Number of set bits
+    ///
+    /// Returns the cached count, which is only accurate if bits are not modified through
+    /// [`DerefMut`] since opening or the last [`MmapBitSlice::recompute_count`].
+    pub fn count_ones(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Flush `len` bits of the [`BitSlice`], starting at bit `offset`
+    ///
+    /// The range is widened to the inner [`BitSlice`] elements it touches. Returns an error if the
+    /// range exceeds the [`BitSlice`].
+    pub fn flush_range(&self, offset: usize, len: usize) -> OperationResult<()> {
+        check_range(offset, len, self.len())?;
+        let bits = usize::BITS as usize;
+        let start = offset / bits;
+        let end = div_ceil(offset + len, bits);
+        let size_t = mem::size_of::<usize>();
+        self.mmap
+            .flush_range(self.header_size + start * size_t, (end - start) * size_t)
+    }
+
+    /// Lock memory mapped pages in memory
+    ///
+    /// See [`MmapType::lock`].
+    pub fn lock(&self) -> io::Result<()> {
+        self.mmap.lock()
+    }
+
+    /// Unlock memory mapped pages previously locked with `lock`
+    ///
+    /// See [`MmapType::unlock`].
+    pub fn unlock(&self) -> io::Result<()> {
+        self.mmap.unlock()
+    }
+
+    /// Advise OS how the mmap will be accessed. On non-Unix platforms this is a no-op.
+    pub fn madvise(&self, advice: Advice) -> io::Result<()> {
+        self.mmap.madvise(advice)
+    }
+
+    /// Populate the page cache by touching every page of the mmap
+    ///
+    /// See [`MmapType::populate`].
+    pub fn populate(&self) {
+        self.mmap.populate();
+    }
+
+    /// Number of bytes of the mmap resident in memory
+    ///
+    /// See [`MmapType::resident_bytes`].
+    pub fn resident_bytes(&self) -> io::Result<usize> {
+        self.mmap.resident_bytes()
+    }
 }
 
 impl Deref for MmapBitSlice {
     type Target = BitSlice;
 
     fn deref(&self) -> &BitSlice {
-        &self.mmap
+        self.bits()
     }
 }
 
 impl DerefMut for MmapBitSlice {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.mmap
+        self.bits_mut()
     }
 }
 
-/// Typed mmap errors.
-#[derive(thiserror::Error, Clone, Debug)]
-pub enum Error {
-    #[error("Mmap length must be {0} to match the size of type, but it is {1}")]
-    SizeExact(usize, usize),
-    #[error("Mmap length must be multiple of {0} to match the size of type, but it is {1}")]
-    SizeMultiple(usize, usize),
-}
-
-/// Get a second mutable reference for type `T` from the given mmap
-///
-/// # Warning
+/// Header `H` followed by a slice of type `T` on a memory mapped file
 ///
-/// The returned reference is unbounded. The user must ensure it never outlives the `mmap` type.
+/// The header is mapped at the start of the mmap. The slice follows directly after it, at the
+/// first offset that is correctly aligned for type `T`.
 ///
 /// # Safety
 ///
-/// - unsafe because we create a second (unbounded) mutable reference
-/// - malformed data in the mmap may break the transmuted type `T` resulting in undefined behavior
-///
-/// # Panics
-///
-/// - panics when the mmap data is not correctly aligned for type `T`
-unsafe fn mmap_to_type_unbounded<'unbnd, T>(mmap: &mut MmapMut) -> Result<&'unbnd mut T>
+/// See [`MmapType`], this directly maps both `H` and `T` onto the memory mapped data.
+pub struct MmapHeaderSlice<H, T>
 where
-    T: Sized,
+    H: 'static,
+    T: 'static,
 {
-    let size_t = mem::size_of::<T>();
+    /// Header accessor: mutable reference to access the header
+    ///
+    /// # Safety
+    ///
+    /// Like the slice in `mmap`, this is an alias to the data inside the mmap. It never overlaps
+    /// the slice.
+    header: &'static mut H,
+    mmap: MmapType<[T]>,
+}
+
+impl<H, T> MmapHeaderSlice<H, T> {
+    /// Transform a mmap into a header of type `H` followed by a slice of type `&[T]`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `H` or `T` resulting in undefined
+    /// behavior.
+    ///
+    /// # Panics
+    ///
+    /// - panics when the mmap is smaller than the header
+    /// - panics when the size of the mmap after the header isn't a multiple of size `T`
+    /// - panics when the mmap data is not correctly aligned for type `H` or `T`
+    /// - See: [`MmapHeaderSlice::try_from`]
+    pub unsafe fn from(mmap: MmapMut) -> Self {
+        Self::try_from(mmap).unwrap()
+    }
+
+    /// Transform a mmap into a header of type `H` followed by a slice of type `&[T]`.
+    ///
+    /// Returns an error when the mmap is smaller than the header, when the size of the mmap after
+    /// the header isn't a multiple of size `T`, or when the mmap data is not correctly aligned for
+    /// type `H` or `T`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `H` or `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn try_from(mut mmap: MmapMut) -> Result<Self> {
+        let (header, slice) = mmap_to_header_slice_unbounded(&mut mmap)?;
+        Ok(Self {
+            header,
+            mmap: MmapType {
+                r#type: slice,
+                mmap: shared_mmap(mmap),
+                checksum: None,
+                zero_on_drop: false,
+                copy_on_write: false,
+                advice: None,
+                scrub: Mutex::new(None),
+            },
+        })
+    }
+
+    /// Offset in bytes of the slice in the mmap, directly after the header
+    pub fn slice_offset() -> usize {
+        let align = mem::align_of::<T>();
+        div_ceil(mem::size_of::<H>(), align) * align
+    }
+
+    /// Header at the start of the mmap
+    pub fn header(&self) -> &H {
+        self.header
+    }
+
+    /// Mutable header at the start of the mmap
+    pub fn header_mut(&mut self) -> &mut H {
+        self.header
+    }
+
+    /// Slice following the header
+    pub fn slice(&self) -> &[T] {
+        &self.mmap
+    }
+
+    /// Mutable slice following the header
+    pub fn slice_mut(&mut self) -> &mut [T] {
+        &mut self.mmap
+    }
+
+    /// Get flusher to explicitly flush mmap at a later time
+    ///
+    /// This flushes both the header and the slice.
+    pub fn flusher(&self) -> Flusher {
+        self.mmap.flusher()
+    }
+}
+
+/// Slice of type `T` on a file, memory mapped as separate fixed size chunks
+///
+/// Mapping a very large file as a single region puts pressure on the TLB and address space. This
+/// maps it in chunks of `chunk_len` elements instead, of which the last one may be shorter. No
+/// element straddles two chunks. Each chunk is a [`MmapSlice`], and can be advised and flushed
+/// independently.
+pub struct MmapChunkedSlice<T>
+where
+    T: Sized + 'static,
+{
+    chunks: Vec<MmapSlice<T>>,
+    chunk_len: usize,
+    len: usize,
+}
+
+impl<T> MmapChunkedSlice<T> {
+    /// Map the file at `path` as slice of type `T`, in chunks of `chunk_len` elements.
+    ///
+    /// The size of a chunk in bytes must be a multiple of the page size, and of the allocation
+    /// granularity on Windows.
+    ///
+    /// Returns an error when the size of the file isn't a multiple of size `T`, or when the chunk
+    /// size is zero or misaligned.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn open(path: &Path, chunk_len: usize) -> OperationResult<Self> {
+        let size_t = mem::size_of::<T>();
+        if size_t == 0 || chunk_len == 0 {
+            return Err(OperationError::service_error(
+                "Chunked mmap slice requires non-zero chunk and element sizes",
+            ));
+        }
+
+        // Every chunk but the first is mapped at a multiple of the chunk size
+        let granularity = mapping_granularity();
+        let chunk_size = chunk_len.saturating_mul(size_t);
+        if chunk_size % granularity != 0 {
+            return Err(Error::ChunkAlignment(granularity, chunk_size).into());
+        }
+
+        let file_len = path.metadata()?.len() as usize;
+        if file_len % size_t != 0 {
+            return Err(Error::SizeMultiple(size_t, file_len).into());
+        }
+
+        let chunks = (0..file_len)
+            .step_by(chunk_size)
+            .map(|offset| -> OperationResult<MmapSlice<T>> {
+                let len = chunk_size.min(file_len - offset);
+                let mmap = mmap_ops::open_write_mmap_range(path, offset, len)?;
+                Ok(MmapSlice::try_from_unchecked(mmap)?)
+            })
+            .collect::<OperationResult<_>>()?;
+
+        Ok(Self {
+            chunks,
+            chunk_len,
+            len: file_len / size_t,
+        })
+    }
+
+    /// Total number of elements over all chunks
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the element at `index`, or `None` if out of bounds
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.chunks
+            .get(index / self.chunk_len)?
+            .get(index % self.chunk_len)
+    }
+
+    /// Get the mutable element at `index`, or `None` if out of bounds
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.chunks
+            .get_mut(index / self.chunk_len)?
+            .get_mut(index % self.chunk_len)
+    }
+
+    /// Chunks of this slice, to advise or flush them separately
+    pub fn chunks(&self) -> &[MmapSlice<T>] {
+        &self.chunks
+    }
+
+    /// Get flusher to explicitly flush all chunks at a later time
+    pub fn flusher(&self) -> Flusher {
+        combine_flushers(self.chunks.iter().map(MmapSlice::flusher).collect())
+    }
+}
+
+/// Header identifying the format of a memory mapped file, see [`MmapHeaderSlice::try_versioned`]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VersionHeader {
+    /// Constant identifying the kind of file
+    pub magic: u32,
+    /// Version of the on-disk layout
+    pub version: u32,
+}
+
+impl<T> MmapHeaderSlice<VersionHeader, T> {
+    /// Write a version header into the mmap, followed by a slice of type `&[T]`.
+    ///
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn new_versioned(mmap: MmapMut, magic: u32, version: u32) -> Result<Self> {
+        let mut mmap = Self::try_from(mmap)?;
+        *mmap.header_mut() = VersionHeader { magic, version };
+        Ok(mmap)
+    }
+
+    /// Transform a mmap into a slice of type `&[T]`, validating its version header.
+    ///
+    /// Returns an error when the magic constant or version in the header does not match, when the
+    /// mmap has an incorrect size, or when the mmap data is not correctly aligned.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn try_versioned(mmap: MmapMut, magic: u32, version: u32) -> Result<Self> {
+        let mmap = Self::try_from(mmap)?;
+        let header = *mmap.header();
+        if header.magic != magic {
+            return Err(Error::MagicMismatch {
+                expected: magic,
+                found: header.magic,
+            });
+        }
+        if header.version != version {
+            return Err(Error::VersionMismatch {
+                expected: version,
+                found: header.version,
+            });
+        }
+        Ok(mmap)
+    }
+}
+
+/// Type `T` on a read-only memory mapped file
+///
+/// Functions as if it is `&T` because this implements [`Deref`]. Unlike [`MmapType`] this is
+/// backed by a [`Mmap`], so the file only needs to be opened for reading.
+///
+/// # Safety
+///
+/// This directly maps (transmutes) the type onto the memory mapped data, see [`MmapType`].
+pub struct MmapTypeReadOnly<T>
+where
+    T: ?Sized + 'static,
+{
+    /// Type accessor: shared reference to access the type
+    ///
+    /// This has the same lifetime as the backing `mmap`, and thus this struct. A borrow must
+    /// never be leased out for longer.
+    r#type: &'static T,
+    /// Type storage: memory mapped file as backing store for type
+    ///
+    /// Keeps ownership of the mmap, and allows properly cleaning up when this struct is dropped.
+    mmap: Mmap,
+}
+
+impl<T> MmapTypeReadOnly<T>
+where
+    T: Sized + 'static,
+{
+    /// Transform a read-only mmap into a typed mmap of type `T`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    ///
+    /// # Panics
+    ///
+    /// - panics when the size of the mmap doesn't match size `T`
+    /// - panics when the mmap data is not correctly aligned for type `T`
+    /// - See: [`MmapTypeReadOnly::try_from`]
+    pub unsafe fn from(mmap_with_type: Mmap) -> Self {
+        Self::try_from(mmap_with_type).unwrap()
+    }
+
+    /// Transform a read-only mmap into a typed mmap of type `T`.
+    ///
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn try_from(mmap_with_type: Mmap) -> Result<Self> {
+        let r#type = mmap_to_type_unbounded_read_only(&mmap_with_type)?;
+        Ok(Self {
+            r#type,
+            mmap: mmap_with_type,
+        })
+    }
+}
+
+impl<T> MmapTypeReadOnly<[T]>
+where
+    T: 'static,
+{
+    /// Transform a read-only mmap into a typed slice mmap of type `&[T]`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    ///
+    /// # Panics
+    ///
+    /// - panics when the size of the mmap isn't a multiple of size `T`
+    /// - panics when the mmap data is not correctly aligned for type `T`
+    /// - See: [`MmapTypeReadOnly::try_slice_from`]
+    pub unsafe fn slice_from(mmap_with_slice: Mmap) -> Self {
+        Self::try_slice_from(mmap_with_slice).unwrap()
+    }
+
+    /// Transform a read-only mmap into a typed slice mmap of type `&[T]`.
+    ///
+    /// Returns an error when the mmap has an incorrect size, or when the mmap data is not
+    /// correctly aligned for type `T`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because malformed data in the mmap may break type `T` resulting in undefined
+    /// behavior.
+    pub unsafe fn try_slice_from(mmap_with_slice: Mmap) -> Result<Self> {
+        let r#type = mmap_to_slice_unbounded_read_only(&mmap_with_slice, 0)?;
+        Ok(Self {
+            r#type,
+            mmap: mmap_with_slice,
+        })
+    }
+}
+
+impl<T> MmapTypeReadOnly<T>
+where
+    T: ?Sized + 'static,
+{
+    /// Get flusher to explicitly flush mmap at a later time
+    ///
+    /// A read-only mmap is never modified, so there is nothing to flush.
+    pub fn flusher(&self) -> Flusher {
+        Box::new(|| Ok(()))
+    }
+
+    /// Lock memory mapped pages in memory
+    ///
+    /// See [`MmapType::lock`].
+    pub fn lock(&self) -> io::Result<()> {
+        lock_pages(&self.mmap)
+    }
+
+    /// Unlock memory mapped pages previously locked with `lock`
+    ///
+    /// See [`MmapType::unlock`].
+    pub fn unlock(&self) -> io::Result<()> {
+        unlock_pages(&self.mmap)
+    }
+
+    /// Advise OS how the mmap will be accessed. On non-Unix platforms this is a no-op.
+    pub fn madvise(&self, advice: Advice) -> io::Result<()> {
+        self.mmap.madvise(advice)
+    }
+
+    /// Populate the page cache by touching every page of the mmap
+    ///
+    /// See [`MmapType::populate`].
+    pub fn populate(&self) {
+        populate(&self.mmap);
+    }
+
+    /// Number of bytes of the mmap resident in memory
+    ///
+    /// See [`MmapType::resident_bytes`].
+    pub fn resident_bytes(&self) -> io::Result<usize> {
+        resident_bytes(&self.mmap)
+    }
+}
+
+impl<T> Deref for MmapTypeReadOnly<T>
+where
+    T: ?Sized + 'static,
+{
+    type Target = T;
+
+    // Has explicit 'bounded lifetime to clarify the inner reference never outlives this struct,
+    // even though the reference has a static lifetime internally.
+    #[allow(clippy::needless_lifetimes)]
+    fn deref<'bounded>(&'bounded self) -> &'bounded Self::Target {
+        self.r#type
+    }
+}
+
+/// Typed mmap errors.
+#[derive(thiserror::Error, Clone, Debug)]
+pub enum Error {
+    #[error("Mmap length must be {0} to match the size of type, but it is {1}")]
+    SizeExact(usize, usize),
+    #[error("Mmap length must be multiple of {0} to match the size of type, but it is {1}")]
+    SizeMultiple(usize, usize),
+    #[error("Mmap data must be aligned to {0} bytes to match the alignment of type")]
+    Alignment(usize),
+    #[error("Mmap header size must be multiple of {0} to match the size of type, but it is {1}")]
+    HeaderSizeMultiple(usize, usize),
+    #[error("Mmap length must be at least the header size {0}, but it is {1}")]
+    HeaderSizeExceeded(usize, usize),
+    #[error("Mmap checksum mismatch, stored {0:#010x} but data has {1:#010x}")]
+    ChecksumMismatch(u32, u32),
+    #[error("Range of {1} at offset {0} exceeds the mmap length of {2}")]
+    RangeExceeded(usize, usize, usize),
+    #[error("Mmap magic constant must be {expected:#010x}, but it is {found:#010x}")]
+    MagicMismatch { expected: u32, found: u32 },
+    #[error("Mmap format version must be {expected}, but it is {found}")]
+    VersionMismatch { expected: u32, found: u32 },
+    #[error("Index {index} is out of bounds for mmap slice of length {len}")]
+    OutOfBounds { index: usize, len: usize },
+    #[error("Mmap offset must be multiple of the mapping granularity {0}, but it is {1}")]
+    OffsetAlignment(usize, usize),
+    #[error("Mmap chunk size must be multiple of the mapping granularity {0}, but it is {1}")]
+    ChunkAlignment(usize, usize),
+}
+
+/// Get a second mutable reference for type `T` from the given mmap
+///
+/// # Warning
+///
+/// The returned reference is unbounded. The user must ensure it never outlives the `mmap` type.
+///
+/// # Safety
+///
+/// - unsafe because we create a second (unbounded) mutable reference
+/// - malformed data in the mmap may break the transmuted type `T` resulting in undefined behavior
+unsafe fn mmap_to_type_unbounded<'unbnd, T>(mmap: &mut MmapMut) -> Result<&'unbnd mut T>
+where
+    T: Sized,
+{
+    // Check size
+    check_type_size::<T>(mmap.len())?;
+
+    // Empty mmap is not supported on Windows, return zero-sized T at dangling pointer instead
+    #[cfg(windows)]
+    if mmap.is_empty() {
+        return Ok(NonNull::dangling().as_mut());
+    }
+
+    // Obtain unbounded bytes slice into mmap
+    let bytes: &'unbnd mut [u8] = {
+        let slice = mmap.deref_mut();
+        slice::from_raw_parts_mut(slice.as_mut_ptr(), slice.len())
+    };
+
+    // Check alignment and size
+    check_alignment::<_, T>(bytes)?;
+    // Checked in release builds as well, a mismatch here would result in undefined behavior
+    assert_eq!(mmap.len(), bytes.len());
+    if bytes.len() != mem::size_of::<T>() {
+        return Err(Error::SizeExact(mem::size_of::<T>(), bytes.len()));
+    }
+
+    let ptr = bytes.as_mut_ptr() as *mut T;
+    Ok(unsafe { &mut *ptr })
+}
+
+/// Get a second mutable reference for the checksum header and type `T` from the given mmap
+///
+/// The mmap must hold a header of [`CHECKSUM_HEADER_SIZE`] bytes, followed by type `T`.
+///
+/// # Warning
+///
+/// The returned references are unbounded. The user must ensure they never outlive the `mmap`
+/// type.
+///
+/// # Safety
+///
+/// - unsafe because we create second (unbounded) mutable references
+/// - malformed data in the mmap may break the transmuted type `T` resulting in undefined behavior
+unsafe fn mmap_to_checksummed_type_unbounded<'unbnd, T>(
+    mmap: &mut MmapMut,
+) -> Result<(&'unbnd mut u32, &'unbnd mut T)>
+where
+    T: Sized,
+{
+    // Check size
+    if mmap.len() < CHECKSUM_HEADER_SIZE {
+        return Err(Error::HeaderSizeExceeded(CHECKSUM_HEADER_SIZE, mmap.len()));
+    }
+    check_type_size::<T>(mmap.len() - CHECKSUM_HEADER_SIZE)?;
+
+    // Obtain unbounded bytes slices into mmap, split at the header
+    let bytes: &'unbnd mut [u8] = {
+        let slice = mmap.deref_mut();
+        slice::from_raw_parts_mut(slice.as_mut_ptr(), slice.len())
+    };
+    let (header, data) = bytes.split_at_mut(CHECKSUM_HEADER_SIZE);
+
+    // Check alignment
+    check_alignment::<_, u32>(header)?;
+    check_alignment::<_, T>(data)?;
+
+    let checksum = header.as_mut_ptr() as *mut u32;
+    let ptr = data.as_mut_ptr() as *mut T;
+    Ok((&mut *checksum, &mut *ptr))
+}
+
+/// Get second mutable references for header `H` and a slice of type `T` after it from the given
+/// mmap
+///
+/// The slice starts at [`MmapHeaderSlice::slice_offset`].
+///
+/// # Warning
+///
+/// The returned references are unbounded. The user must ensure they never outlive the `mmap`
+/// type.
+///
+/// # Safety
+///
+/// - unsafe because we create second (unbounded) mutable references
+/// - malformed data in the mmap may break the transmuted type `H` or slice for type `T` resulting
+///   in undefined behavior
+unsafe fn mmap_to_header_slice_unbounded<'unbnd, H, T>(
+    mmap: &mut MmapMut,
+) -> Result<(&'unbnd mut H, &'unbnd mut [T])>
+where
+    H: Sized,
+    T: Sized,
+{
+    let size_t = mem::size_of::<T>();
+    let offset = MmapHeaderSlice::<H, T>::slice_offset();
+
+    // Check size
+    if offset > mmap.len() {
+        return Err(Error::HeaderSizeExceeded(offset, mmap.len()));
+    }
+    if size_t == 0 {
+        // For zero-sized T, data part must be zero-sized as well, we cannot have infinite slice
+        if mmap.len() != offset {
+            return Err(Error::SizeExact(offset, mmap.len()));
+        }
+    } else if (mmap.len() - offset) % size_t != 0 {
+        return Err(Error::SizeMultiple(size_t, mmap.len() - offset));
+    }
+
+    // Empty mmap is not supported on Windows, return zero-sized header and empty slice at
+    // dangling pointers instead
+    #[cfg(windows)]
+    if mmap.is_empty() {
+        let dangling = NonNull::dangling();
+        return Ok((
+            NonNull::dangling().as_mut(),
+            slice::from_raw_parts_mut(dangling.as_ptr(), 0),
+        ));
+    }
+
+    // Obtain unbounded bytes slices into mmap, split after the header
+    let bytes: &'unbnd mut [u8] = {
+        let slice = mmap.deref_mut();
+        slice::from_raw_parts_mut(slice.as_mut_ptr(), slice.len())
+    };
+    let (header, data) = bytes.split_at_mut(offset);
+
+    // Check alignment, data is aligned for T if the mmap is aligned for H
+    check_alignment::<_, H>(header)?;
+    check_alignment::<_, T>(data)?;
+
+    let header = header.as_mut_ptr() as *mut H;
+    Ok((
+        &mut *header,
+        slice::from_raw_parts_mut(
+            data.as_mut_ptr() as *mut T,
+            data.len().checked_div(size_t).unwrap_or(0),
+        ),
+    ))
+}
+
+/// Get a second mutable reference for a slice of type `T` from the given mmap
+///
+/// A (non-zero) header size in bytes may be provided to omit from the BitSlice data.
+///
+/// On Windows, if an empty mmap is provided. An empty slice at dangling pointer is returned.
+///
+/// # Warning
+///
+/// The returned reference is unbounded. The user must ensure it never outlives the `mmap` type.
+///
+/// # Safety
+///
+/// - unsafe because we create a second (unbounded) mutable reference
+/// - malformed data in the mmap may break the transmuted slice for type `T` resulting in undefined
+///   behavior
+unsafe fn mmap_to_slice_unbounded<'unbnd, T>(
+    mmap: &mut MmapMut,
+    header_size: usize,
+) -> Result<&'unbnd mut [T]>
+where
+    T: Sized,
+{
+    let size_t = mem::size_of::<T>();
+
+    // Check size
+    check_slice_size::<T>(mmap.len(), header_size)?;
+
+    // Empty mmap is not supported on Windows, return empty slice at dangling pointer instead
+    #[cfg(windows)]
+    if mmap.is_empty() {
+        let dangling = NonNull::dangling();
+        return Ok(slice::from_raw_parts_mut(dangling.as_ptr(), 0));
+    }
+
+    // Obtain unbounded bytes slice into mmap
+    let bytes: &'unbnd mut [u8] = {
+        let slice = mmap.deref_mut();
+        &mut slice::from_raw_parts_mut(slice.as_mut_ptr(), slice.len())[header_size..]
+    };
+
+    // Check alignment and bytes size
+    check_alignment::<_, T>(bytes)?;
+    // Checked in release builds as well, a mismatch here would result in undefined behavior
+    assert_eq!(bytes.len() + header_size, mmap.len());
+
+    // Transmute slice types
+    Ok(slice::from_raw_parts_mut(
+        bytes.as_mut_ptr() as *mut T,
+        bytes.len().checked_div(size_t).unwrap_or(0),
+    ))
+}
+
+/// Get a reference for type `T` from the given read-only mmap
+///
+/// # Warning
+///
+/// The returned reference is unbounded. The user must ensure it never outlives the `mmap` type.
+///
+/// # Safety
+///
+/// - unsafe because we create an unbounded reference
+/// - malformed data in the mmap may break the transmuted type `T` resulting in undefined behavior
+unsafe fn mmap_to_type_unbounded_read_only<'unbnd, T>(mmap: &Mmap) -> Result<&'unbnd T>
+where
+    T: Sized,
+{
+    // Check size
+    check_type_size::<T>(mmap.len())?;
+
+    // Empty mmap is not supported on Windows, return zero-sized T at dangling pointer instead
+    #[cfg(windows)]
+    if mmap.is_empty() {
+        return Ok(NonNull::dangling().as_ref());
+    }
+
+    // Obtain unbounded bytes slice into mmap
+    let bytes: &'unbnd [u8] = slice::from_raw_parts(mmap.as_ptr(), mmap.len());
+
+    // Check alignment
+    check_alignment::<_, T>(bytes)?;
+
+    let ptr = bytes.as_ptr() as *const T;
+    Ok(unsafe { &*ptr })
+}
+
+/// Get a reference for a slice of type `T` from the given read-only mmap
+///
+/// A (non-zero) header size in bytes may be provided to omit from the slice data.
+///
+/// # Warning
+///
+/// The returned reference is unbounded. The user must ensure it never outlives the `mmap` type.
+///
+/// # Safety
+///
+/// - unsafe because we create an unbounded reference
+/// - malformed data in the mmap may break the transmuted slice for type `T` resulting in undefined
+///   behavior
+unsafe fn mmap_to_slice_unbounded_read_only<'unbnd, T>(
+    mmap: &Mmap,
+    header_size: usize,
+) -> Result<&'unbnd [T]>
+where
+    T: Sized,
+{
+    let size_t = mem::size_of::<T>();
+
+    // Check size
+    check_slice_size::<T>(mmap.len(), header_size)?;
+
+    // Empty mmap is not supported on Windows, return empty slice at dangling pointer instead
+    #[cfg(windows)]
+    if mmap.is_empty() {
+        let dangling = NonNull::dangling();
+        return Ok(slice::from_raw_parts(dangling.as_ptr(), 0));
+    }
+
+    // Obtain unbounded bytes slice into mmap
+    let bytes: &'unbnd [u8] = &slice::from_raw_parts(mmap.as_ptr(), mmap.len())[header_size..];
+
+    // Check alignment
+    check_alignment::<_, T>(bytes)?;
+
+    // Transmute slice types
+    Ok(slice::from_raw_parts(
+        bytes.as_ptr() as *const T,
+        bytes.len().checked_div(size_t).unwrap_or(0),
+    ))
+}
+
+/// Check a mmap of `len` bytes matches the size of type `T`.
+fn check_type_size<T>(len: usize) -> Result<()> {
+    let size_t = mem::size_of::<T>();
+    if len != size_t {
+        return Err(Error::SizeExact(size_t, len));
+    }
+    Ok(())
+}
+
+/// Check a mmap of `len` bytes fits a header of `header_size` bytes and a slice of type `T`.
+fn check_slice_size<T>(len: usize, header_size: usize) -> Result<()> {
+    let size_t = mem::size_of::<T>();
+    if header_size > len {
+        return Err(Error::HeaderSizeExceeded(header_size, len));
+    }
+    if size_t == 0 {
+        // For zero-sized T, data part must be zero-sized as well, we cannot have infinite slice
+        if len != header_size {
+            return Err(Error::SizeExact(header_size, len));
+        }
+    } else {
+        // Must be multiple of size T
+        if header_size % size_t != 0 {
+            return Err(Error::HeaderSizeMultiple(size_t, header_size));
+        }
+        if len % size_t != 0 {
+            return Err(Error::SizeMultiple(size_t, len));
+        }
+    }
+    Ok(())
+}
+
+/// Touch every page of the given mmap data, to fault them into memory.
+///
+/// Does nothing if the data is larger than [`MAX_POPULATE_SIZE`].
+fn populate(bytes: &[u8]) {
+    if bytes.len() > MAX_POPULATE_SIZE {
+        log::debug!(
+            "Not populating mmap of {} bytes, larger than {MAX_POPULATE_SIZE} bytes",
+            bytes.len(),
+        );
+        return;
+    }
+
+    for offset in (0..bytes.len()).step_by(POPULATE_STRIDE) {
+        // Volatile read, so the compiler cannot elide touching the page
+        let _ = unsafe { std::ptr::read_volatile(bytes.as_ptr().add(offset)) };
+    }
+}
+
+/// Find the index of the first value that is not finite.
+fn first_non_finite<T: Float>(values: &[T]) -> Option<usize> {
+    for (chunk_index, chunk) in values.chunks(VALIDATE_CHUNK_SIZE).enumerate() {
+        // Check the whole chunk without branching, so it can be vectorized
+        let finite = chunk
+            .iter()
+            .fold(true, |finite, value| finite & value.is_finite());
+        if !finite {
+            let position = chunk.iter().position(|value| !value.is_finite())?;
+            return Some(chunk_index * VALIDATE_CHUNK_SIZE + position);
+        }
+    }
+    None
+}
+
+/// Count the number of set bits, using popcount over the inner [`BitSlice`] elements.
+fn popcount(bitslice: &BitSlice) -> usize {
+    bitslice
+        .as_raw_slice()
+        .iter()
+        .map(|word| word.count_ones() as usize)
+        .sum()
+}
+
+/// Lock the pages of the given mmap data in memory.
+#[cfg(unix)]
+fn lock_pages(bytes: &[u8]) -> io::Result<()> {
+    let result = unsafe { libc::mlock(bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Unlock the pages of the given mmap data.
+#[cfg(unix)]
+fn unlock_pages(bytes: &[u8]) -> io::Result<()> {
+    let result = unsafe { libc::munlock(bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Lock the pages of the given mmap data in memory.
+#[cfg(windows)]
+fn lock_pages(bytes: &[u8]) -> io::Result<()> {
+    use windows_sys::Win32::System::Memory::VirtualLock;
+
+    // Locking an empty range fails on Windows, while there is nothing to lock
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    let result = unsafe { VirtualLock(bytes.as_ptr() as *const _, bytes.len()) };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Unlock the pages of the given mmap data.
+#[cfg(windows)]
+fn unlock_pages(bytes: &[u8]) -> io::Result<()> {
+    use windows_sys::Win32::System::Memory::VirtualUnlock;
+
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    let result = unsafe { VirtualUnlock(bytes.as_ptr() as *const _, bytes.len()) };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Lock the pages of the given mmap data in memory.
+#[cfg(not(any(unix, windows)))]
+fn lock_pages(_bytes: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Locking mmaps is not supported on this platform",
+    ))
+}
+
+/// Unlock the pages of the given mmap data.
+#[cfg(not(any(unix, windows)))]
+fn unlock_pages(_bytes: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unlocking mmaps is not supported on this platform",
+    ))
+}
+
+/// Alignment in bytes required for the start of a mapping within a file
+///
+/// This is the page size, see [`mmap_ops::page_size`], and the allocation granularity on
+/// Windows.
+#[cfg(not(windows))]
+pub fn mapping_granularity() -> usize {
+    mmap_ops::page_size()
+}
+
+/// Alignment in bytes required for the start of a mapping within a file
+///
+/// This is the page size, see [`mmap_ops::page_size`], and the allocation granularity on
+/// Windows.
+#[cfg(windows)]
+pub fn mapping_granularity() -> usize {
+    use windows_sys::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+
+    let mut info: SYSTEM_INFO = unsafe { mem::zeroed() };
+    unsafe { GetSystemInfo(&mut info) };
+    info.dwAllocationGranularity as usize
+}
+
+/// Count the bytes of the given mmap data that are resident in memory.
+#[cfg(target_os = "linux")]
+fn resident_bytes(bytes: &[u8]) -> io::Result<usize> {
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+
+    let page_size = mmap_ops::page_size();
+    let mut residency = vec![0u8; div_ceil(bytes.len(), page_size)];
+    // Safety: the mmap data starts at a page boundary, and the residency vector has an entry for
+    // every page
+    let result = unsafe {
+        libc::mincore(
+            bytes.as_ptr() as *mut libc::c_void,
+            bytes.len(),
+            residency.as_mut_ptr(),
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Lowest bit marks a resident page
+    let resident_pages = residency.iter().filter(|&&page| page & 1 == 1).count();
+    Ok((resident_pages * page_size).min(bytes.len()))
+}
+
+/// Count the bytes of the given mmap data that are resident in memory.
+#[cfg(not(target_os = "linux"))]
+fn resident_bytes(_bytes: &[u8]) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Resident memory of mmaps is only reported on Linux",
+    ))
+}
+
+/// Share the given mmap, and register it to follow changes of the global advice.
+///
+/// See [`madvise::register`].
+fn shared_mmap(mmap: MmapMut) -> Arc<MmapMut> {
+    let mmap = Arc::new(mmap);
+    madvise::register(&mmap);
+    mmap
+}
+
+/// Check a range of `len` at `offset` fits within `total`.
+fn check_range(offset: usize, len: usize, total: usize) -> Result<()> {
+    match offset.checked_add(len) {
+        Some(end) if end <= total => Ok(()),
+        _ => Err(Error::RangeExceeded(offset, len, total)),
+    }
+}
+
+/// Check slice `&[S]` is correctly aligned for type `T`.
+fn check_alignment<S, T>(bytes: &[S]) -> Result<()> {
+    let align_t = mem::align_of::<T>();
+    if bytes.as_ptr().align_offset(align_t) != 0 {
+        return Err(Error::Alignment(align_t));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+    use std::iter;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use tempfile::{Builder, NamedTempFile};
+
+    use super::*;
+
+    fn create_temp_mmap_file(len: usize) -> NamedTempFile {
+        let tempfile = Builder::new()
+            .prefix("test.")
+            .suffix(".mmap")
+            .tempfile()
+            .unwrap();
+        tempfile.as_file().set_len(len as u64).unwrap();
+        tempfile
+    }
+
+    #[test]
+    fn test_open_zero_type() {
+        check_open_zero_type::<()>(());
+        check_open_zero_type::<u8>(0);
+        check_open_zero_type::<usize>(0);
+        check_open_zero_type::<f32>(0.0);
+    }
+
+    fn check_open_zero_type<T: Sized + PartialEq + Debug + 'static>(zero: T) {
+        let bytes = mem::size_of::<T>();
+        let tempfile = create_temp_mmap_file(bytes);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+
+        let mmap_type: MmapType<T> = unsafe { MmapType::from(mmap) };
+        assert_eq!(mmap_type.deref(), &zero);
+    }
+
+    #[test]
+    fn test_open_zero_slice() {
+        check_open_zero_slice::<()>(0, ());
+        check_open_zero_slice::<u8>(0, 0);
+        check_open_zero_slice::<u8>(1, 0);
+        check_open_zero_slice::<u8>(131, 0);
+        check_open_zero_slice::<usize>(0, 0);
+        check_open_zero_slice::<usize>(1, 0);
+        check_open_zero_slice::<usize>(131, 0);
+        check_open_zero_slice::<f32>(0, 0.0);
+        check_open_zero_slice::<f32>(1, 0.0);
+        check_open_zero_slice::<f32>(131, 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_open_zero_slice_infinite_length() {
+        // A slice with zero-sized type T can never be more than 0 bytes
+        check_open_zero_slice::<()>(1, ());
+    }
+
+    fn check_open_zero_slice<T: Sized + PartialEq + Debug + 'static>(len: usize, zero: T) {
+        let bytes = mem::size_of::<T>() * len;
+        let tempfile = create_temp_mmap_file(bytes);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+
+        let mmap_slice: MmapSlice<T> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice.len(), len);
+        assert!(mmap_slice.iter().all(|i| i == &zero));
+    }
+
+    #[test]
+    fn test_reopen_random() {
+        let mut rng = StdRng::seed_from_u64(42);
+        check_reopen_random::<(), _>(0, || rng.gen());
+        check_reopen_random::<u8, _>(0, || rng.gen());
+        check_reopen_random::<u8, _>(1, || rng.gen());
+        check_reopen_random::<u8, _>(131, || rng.gen());
+        check_reopen_random::<usize, _>(0, || rng.gen());
+        check_reopen_random::<usize, _>(1, || rng.gen());
+        check_reopen_random::<usize, _>(131, || rng.gen());
+        check_reopen_random::<f32, _>(0, || rng.gen());
+        check_reopen_random::<f32, _>(1, || rng.gen());
+        check_reopen_random::<f32, _>(131, || rng.gen());
+    }
+
+    fn check_reopen_random<T, R>(len: usize, rng: R)
+    where
+        T: Sized + Copy + PartialEq + Debug + 'static,
+        R: FnMut() -> T,
+    {
+        let bytes = mem::size_of::<T>() * len;
+        let tempfile = create_temp_mmap_file(bytes);
+
+        let template: Vec<T> = iter::repeat_with(rng).take(len).collect();
+
+        // Write random values from template into mmap
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap_slice: MmapSlice<T> = unsafe { MmapSlice::from(mmap) };
+            assert_eq!(mmap_slice.len(), len);
+            mmap_slice.copy_from_slice(&template);
+        }
+
+        // Reopen and assert values from template
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mmap_slice: MmapSlice<T> = unsafe { MmapSlice::from(mmap) };
+            assert_eq!(mmap_slice.as_ref(), template);
+        }
+
+        // Reopen read-only and assert values from template
+        {
+            let mmap = mmap_ops::open_read_mmap(tempfile.path()).unwrap();
+            let mmap_slice: MmapTypeReadOnly<[T]> = unsafe { MmapTypeReadOnly::slice_from(mmap) };
+            assert_eq!(mmap_slice.deref(), template);
+            mmap_slice.flusher()().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_resize_slice() {
+        let tempfile = create_temp_mmap_file(4 * mem::size_of::<u64>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.copy_from_slice(&[1, 2, 3, 4]);
+        let old_flusher = mmap_slice.flusher();
+
+        unsafe { mmap_slice.resize(tempfile.path(), 6).unwrap() };
+        assert_eq!(mmap_slice.as_ref(), &[1, 2, 3, 4, 0, 0]);
+        mmap_slice[5] = 6;
+        // Does nothing, the previous mmap is already flushed and released
+        old_flusher().unwrap();
+        mmap_slice.flusher()().unwrap();
+
+        // Shrinking is not supported
+        assert!(unsafe { mmap_slice.resize(tempfile.path(), 2) }.is_err());
+        assert_eq!(mmap_slice.len(), 6);
+        drop(mmap_slice);
+
+        // Reopen and assert values survived growing
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice.as_ref(), &[1, 2, 3, 4, 0, 6]);
+    }
+
+    #[test]
+    fn test_truncate_slice() {
+        let tempfile = create_temp_mmap_file(6 * mem::size_of::<u64>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        unsafe { mmap_slice.truncate(tempfile.path(), 4).unwrap() };
+        assert_eq!(mmap_slice.as_ref(), &[1, 2, 3, 4]);
+        assert_eq!(tempfile.as_file().metadata().unwrap().len(), 32);
+
+        // Growing is not supported
+        assert!(unsafe { mmap_slice.truncate(tempfile.path(), 5) }.is_err());
+        assert_eq!(mmap_slice.len(), 4);
+
+        unsafe { mmap_slice.truncate(tempfile.path(), 0).unwrap() };
+        assert!(mmap_slice.is_empty());
+    }
+
+    #[test]
+    fn test_resize_advice() {
+        let tempfile = create_temp_mmap_file(4 * mem::size_of::<u64>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+
+        unsafe { mmap_slice.resize(tempfile.path(), 6).unwrap() };
+        assert_eq!(mmap_slice.advice(), madvise::get_global());
+
+        mmap_slice.set_advice(Advice::Sequential).unwrap();
+        unsafe { mmap_slice.resize(tempfile.path(), 8).unwrap() };
+        unsafe { mmap_slice.resize(tempfile.path(), 10).unwrap() };
+        assert_eq!(mmap_slice.advice(), Advice::Sequential);
+    }
+
+    #[test]
+    fn test_flush_range() {
+        let tempfile = create_temp_mmap_file(8 * mem::size_of::<u64>());
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_type: MmapType<[u64; 8]> = unsafe { MmapType::from(mmap) };
+        mmap_type[1] = 1;
+        mmap_type.flush_range(8, 8).unwrap();
+        mmap_type.flush_async().unwrap();
+        mmap_type.async_flusher()().unwrap();
+        mmap_type.flush_range(0, 64).unwrap();
+        mmap_type.flush_range(64, 0).unwrap();
+        assert!(mmap_type.flush_range(60, 8).is_err());
+        assert!(mmap_type.flush_range(usize::MAX, 2).is_err());
+        drop(mmap_type);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice[1], 1);
+        mmap_slice[7] = 7;
+        mmap_slice.flush_range(7, 1).unwrap();
+        mmap_slice.flush_range(0, 8).unwrap();
+        assert!(mmap_slice.flush_range(7, 2).is_err());
+        drop(mmap_slice);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_bitslice = MmapBitSlice::from(mmap, mem::size_of::<usize>());
+        let bits = mmap_bitslice.len();
+        mmap_bitslice.set(bits - 1, true);
+        mmap_bitslice.flush_range(bits - 1, 1).unwrap();
+        mmap_bitslice.flush_range(3, 70).unwrap();
+        mmap_bitslice.flush_range(0, bits).unwrap();
+        assert!(mmap_bitslice.flush_range(bits - 1, 2).is_err());
+    }
+
+    #[test]
+    fn test_flush_on_drop() {
+        let tempfile = create_temp_mmap_file(mem::size_of::<u64>());
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_type: MmapType<u64> = unsafe { MmapType::from(mmap) };
+        *mmap_type = 42;
+        let flusher = mmap_type.flusher();
+        let async_flusher = mmap_type.async_flusher();
+        drop(mmap_type);
+
+        // Flushers are skipped, the mmap was already flushed when dropped
+        flusher().unwrap();
+        async_flusher().unwrap();
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapType<u64> = unsafe { MmapType::from(mmap) };
+        assert_eq!(*mmap_type, 42);
+    }
+
+    #[test]
+    fn test_header_slice() {
+        #[repr(C)]
+        #[derive(Debug, PartialEq)]
+        struct Header {
+            version: u32,
+            kind: u8,
+        }
+
+        // Slice is aligned after the header padding
+        assert_eq!(MmapHeaderSlice::<Header, u64>::slice_offset(), 8);
+        assert_eq!(MmapHeaderSlice::<[u8; 3], u32>::slice_offset(), 4);
+        assert_eq!(MmapHeaderSlice::<[u8; 3], u8>::slice_offset(), 3);
+
+        let tempfile = create_temp_mmap_file(8 + 4 * mem::size_of::<u64>());
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap: MmapHeaderSlice<Header, u64> = unsafe { MmapHeaderSlice::from(mmap) };
+            assert_eq!(mmap.slice().len(), 4);
+            *mmap.header_mut() = Header {
+                version: 1,
+                kind: 2,
+            };
+            mmap.slice_mut().copy_from_slice(&[3, 4, 5, 6]);
+            mmap.flusher()().unwrap();
+        }
+
+        // Reopen and assert header and values
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap: MmapHeaderSlice<Header, u64> = unsafe { MmapHeaderSlice::from(mmap) };
+        assert_eq!(
+            mmap.header(),
+            &Header {
+                version: 1,
+                kind: 2,
+            },
+        );
+        assert_eq!(mmap.slice(), &[3, 4, 5, 6]);
+
+        let tempfile = create_temp_mmap_file(12);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let result = unsafe { MmapHeaderSlice::<Header, u64>::try_from(mmap) };
+        assert!(matches!(result, Err(Error::SizeMultiple(8, 4))));
+
+        let tempfile = create_temp_mmap_file(4);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let result = unsafe { MmapHeaderSlice::<Header, u64>::try_from(mmap) };
+        assert!(matches!(result, Err(Error::HeaderSizeExceeded(8, 4))));
+    }
+
+    #[test]
+    fn test_versioned() {
+        const MAGIC: u32 = 0x5144_5254;
+
+        let tempfile = create_temp_mmap_file(8 + 2 * mem::size_of::<u32>());
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap: MmapHeaderSlice<VersionHeader, u32> =
+                unsafe { MmapHeaderSlice::new_versioned(mmap, MAGIC, 2).unwrap() };
+            mmap.slice_mut().copy_from_slice(&[1, 2]);
+        }
+
+        let open = |magic, version| {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            unsafe { MmapHeaderSlice::<VersionHeader, u32>::try_versioned(mmap, magic, version) }
+        };
+        assert_eq!(open(MAGIC, 2).unwrap().slice(), &[1, 2]);
+        assert!(matches!(
+            open(MAGIC, 3),
+            Err(Error::VersionMismatch {
+                expected: 3,
+                found: 2,
+            }),
+        ));
+        assert!(matches!(
+            open(0, 2),
+            Err(Error::MagicMismatch {
+                expected: 0,
+                found: MAGIC,
+            }),
+        ));
+    }
+
+    #[test]
+    fn test_zero_on_drop() {
+        let tempfile = create_temp_mmap_file(mem::size_of::<u64>());
+
+        // Zeros are written to the file through a shared mapping
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_type = unsafe { MmapType::<u64>::try_from_zero_on_drop(mmap).unwrap() };
+        *mmap_type = 42;
+        drop(mmap_type);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_type: MmapType<u64> = unsafe { MmapType::from(mmap) };
+        assert_eq!(*mmap_type, 0);
+        *mmap_type = 42;
+        drop(mmap_type);
+
+        // File is kept as is with a copy-on-write mapping
+        let file = std::fs::File::open(tempfile.path()).unwrap();
+        let mmap = unsafe { memmap2::MmapOptions::new().map_copy(&file).unwrap() };
+        let mut mmap_type = unsafe { MmapType::<u64>::try_from_zero_on_drop(mmap).unwrap() };
+        assert_eq!(*mmap_type, 42);
+        *mmap_type = 7;
+        drop(mmap_type);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapType<u64> = unsafe { MmapType::from(mmap) };
+        assert_eq!(*mmap_type, 42);
+    }
+
+    #[test]
+    fn test_as_bytes() {
+        let tempfile = create_temp_mmap_file(2 * mem::size_of::<u32>());
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_type: MmapType<[u32; 2]> = unsafe { MmapType::from(mmap) };
+        *mmap_type = [1, u32::MAX];
+        let bytes = [1u32.to_ne_bytes(), u32::MAX.to_ne_bytes()].concat();
+        assert_eq!(mmap_type.as_bytes(), bytes);
+        unsafe { mmap_type.as_bytes_mut()[..4].copy_from_slice(&2u32.to_ne_bytes()) };
+        assert_eq!(mmap_type.deref(), &[2, u32::MAX]);
+        drop(mmap_type);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u32> = unsafe { MmapSlice::from(mmap) };
+        let bytes = [2u32.to_ne_bytes(), u32::MAX.to_ne_bytes()].concat();
+        assert_eq!(mmap_slice.as_bytes(), bytes);
+        unsafe { mmap_slice.as_bytes_mut()[4..].fill(0) };
+        assert_eq!(mmap_slice.as_ref(), &[2, 0]);
+    }
+
+    #[test]
+    fn test_create() {
+        let dir = Builder::new().prefix("mmap_create").tempdir().unwrap();
+        let path = dir.path().join("test.mmap");
+
+        let mmap_type: MmapType<[u64; 3]> = unsafe { MmapType::create(&path).unwrap() };
+        assert_eq!(mmap_type.deref(), &[0; 3]);
+        drop(mmap_type);
+
+        let mut mmap_slice: MmapSlice<u32> = unsafe { MmapSlice::create(&path, 5).unwrap() };
+        assert_eq!(mmap_slice.as_ref(), &[0; 5]);
+        mmap_slice[4] = 4;
+        drop(mmap_slice);
+
+        // Existing file is replaced with zeros
+        let mmap_slice: MmapSlice<u32> = unsafe { MmapSlice::create(&path, 5).unwrap() };
+        assert_eq!(mmap_slice.as_ref(), &[0; 5]);
+        drop(mmap_slice);
+
+        let mmap_bitslice = MmapBitSlice::create(&path, 65, 8).unwrap();
+        assert_eq!(mmap_bitslice.len(), 128);
+        assert_eq!(mmap_bitslice.count_ones(), 0);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 8 + 16);
+    }
+
+    #[test]
+    fn test_into_readonly() {
+        let dir = Builder::new().prefix("mmap_readonly").tempdir().unwrap();
+        let path = dir.path().join("test.mmap");
+
+        let mut mmap_type: MmapType<[u64; 3]> = unsafe { MmapType::create(&path).unwrap() };
+        *mmap_type = [1, 2, 3];
+        let flusher = mmap_type.flusher();
+
+        let mmap_type = mmap_type.into_readonly().unwrap();
+        assert_eq!(mmap_type.deref(), &[1, 2, 3]);
+        flusher().unwrap();
+        drop(mmap_type);
+
+        // Flushed before downgrading
+        let mmap = mmap_ops::open_read_mmap(&path).unwrap();
+        let mmap_type: MmapTypeReadOnly<[u64; 3]> = unsafe { MmapTypeReadOnly::from(mmap) };
+        assert_eq!(mmap_type.deref(), &[1, 2, 3]);
+
+        let mmap = mmap_ops::open_write_mmap(&path).unwrap();
+        let mmap_type: MmapType<[u64; 3]> =
+            unsafe { MmapType::try_from_zero_on_drop(mmap).unwrap() };
+        assert!(mmap_type.into_readonly().is_err());
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let tempfile = create_temp_mmap_file(mem::size_of::<u64>());
+
+        let mut mmap_type = unsafe { MmapType::<u64>::from_cow(tempfile.path()).unwrap() };
+        *mmap_type = 42;
+        assert_eq!(*mmap_type, 42);
+        mmap_type.flusher()().unwrap();
+        mmap_type.async_flusher()().unwrap();
+        drop(mmap_type);
+
+        // Changes never reach the file
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapType<u64> = unsafe { MmapType::from(mmap) };
+        assert_eq!(*mmap_type, 0);
+    }
+
+    #[test]
+    fn test_validate_finite() {
+        let tempfile = create_temp_mmap_file(100 * mem::size_of::<f32>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<f32> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice[99] = f32::MIN_POSITIVE / 2.0;
+        assert_eq!(mmap_slice.validate_finite(), Ok(()));
+        mmap_slice[99] = f32::INFINITY;
+        assert_eq!(mmap_slice.validate_finite(), Err(99));
+        mmap_slice[37] = f32::NAN;
+        assert_eq!(mmap_slice.validate_finite(), Err(37));
+        drop(mmap_slice);
+
+        let tempfile = create_temp_mmap_file(3 * mem::size_of::<f64>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<f64> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice.validate_finite(), Ok(()));
+        mmap_slice[0] = f64::NEG_INFINITY;
+        assert_eq!(mmap_slice.validate_finite(), Err(0));
+    }
+
+    #[test]
+    fn test_chunked_slice() {
+        // Chunks of 64 KiB, a multiple of the page size on all platforms
+        let chunk_len = 64 * 1024 / mem::size_of::<u64>();
+        let len = 2 * chunk_len + 3;
+        let tempfile = create_temp_mmap_file(len * mem::size_of::<u64>());
+
+        {
+            let mut mmap =
+                unsafe { MmapChunkedSlice::<u64>::open(tempfile.path(), chunk_len) }.unwrap();
+            assert_eq!(mmap.len(), len);
+            assert_eq!(mmap.chunks().len(), 3);
+            assert_eq!(mmap.chunks()[2].len(), 3);
+            for index in [0, chunk_len - 1, chunk_len, len - 1] {
+                *mmap.get_mut(index).unwrap() = index as u64;
+            }
+            assert!(mmap.get_mut(len).is_none());
+            mmap.chunks()[0].madvise(Advice::Sequential).unwrap();
+            mmap.flusher()().unwrap();
+        }
+
+        // Reopen as a single slice and with other chunks, and assert values
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice[chunk_len], chunk_len as u64);
+        assert_eq!(mmap_slice[len - 1], (len - 1) as u64);
+        drop(mmap_slice);
+
+        let mmap =
+            unsafe { MmapChunkedSlice::<u64>::open(tempfile.path(), 2 * chunk_len) }.unwrap();
+        assert_eq!(mmap.chunks().len(), 2);
+        for index in [0, chunk_len - 1, chunk_len, len - 1] {
+            assert_eq!(mmap.get(index), Some(&(index as u64)));
+        }
+        assert_eq!(mmap.get(1), Some(&0));
+        assert_eq!(mmap.get(len), None);
+
+        assert!(unsafe { MmapChunkedSlice::<u64>::open(tempfile.path(), 0) }.is_err());
+    }
+
+    #[test]
+    fn test_chunked_slice_misaligned() {
+        let granularity = mapping_granularity();
+        let tempfile = create_temp_mmap_file(2 * granularity);
+        let open = |chunk_len| unsafe { MmapChunkedSlice::<u64>::open(tempfile.path(), chunk_len) };
+
+        let chunk_len = granularity / mem::size_of::<u64>();
+        assert_eq!(open(chunk_len).unwrap().chunks().len(), 2);
+
+        // Later chunks would be mapped at offsets that are not a multiple of the granularity
+        for chunk_len in [3, chunk_len - 1, chunk_len + 1, chunk_len + chunk_len / 2] {
+            assert!(open(chunk_len).is_err(), "chunk of {chunk_len} elements");
+        }
+        assert!(open(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_get_checked() {
+        let tempfile = create_temp_mmap_file(3 * mem::size_of::<u32>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u32> = unsafe { MmapSlice::from(mmap) };
+
+        *mmap_slice.try_get_mut(2).unwrap() = 2;
+        assert_eq!(mmap_slice.get_checked(2), Some(&2));
+        assert_eq!(mmap_slice.get_checked(3), None);
+        assert_eq!(mmap_slice.try_get(2).unwrap(), &2);
+        assert!(matches!(
+            mmap_slice.try_get(3),
+            Err(Error::OutOfBounds { index: 3, len: 3 }),
+        ));
+        assert!(matches!(
+            mmap_slice.try_get_mut(usize::MAX),
+            Err(Error::OutOfBounds {
+                index: usize::MAX,
+                len: 3,
+            }),
+        ));
+    }
+
+    #[test]
+    fn test_pod() {
+        let tempfile = create_temp_mmap_file(4 * mem::size_of::<u32>());
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u32> = MmapSlice::from_pod(mmap);
+        mmap_slice.copy_from_slice(&[1, 2, 3, 4]);
+        mmap_slice.flusher()().unwrap();
+        drop(mmap_slice);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapType<[u32; 4]> = MmapType::from_pod(mmap);
+        assert_eq!(mmap_type.deref(), &[1, 2, 3, 4]);
+        drop(mmap_type);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let result = MmapType::<[u32; 3]>::try_from_pod(mmap);
+        assert!(matches!(result, Err(Error::SizeExact(12, 16))));
+    }
+
+    #[test]
+    fn test_read_only_type() {
+        let tempfile = create_temp_mmap_file(mem::size_of::<u64>());
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap_type: MmapType<u64> = unsafe { MmapType::from(mmap) };
+            *mmap_type = 42;
+            mmap_type.flusher()().unwrap();
+        }
+
+        let mmap = mmap_ops::open_read_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapTypeReadOnly<u64> = unsafe { MmapTypeReadOnly::from(mmap) };
+        assert_eq!(*mmap_type, 42);
+
+        let mmap = mmap_ops::open_read_mmap(tempfile.path()).unwrap();
+        let result = unsafe { MmapTypeReadOnly::<u32>::try_from(mmap) };
+        assert!(matches!(result, Err(Error::SizeExact(4, 8))));
+    }
+
+    #[test]
+    fn test_bitslice() {
+        check_bitslice_with_header(0, 0);
+        check_bitslice_with_header(0, 128);
+        check_bitslice_with_header(512, 0);
+        check_bitslice_with_header(512, 256);
+        check_bitslice_with_header(11721 * 8, 256);
+    }
+
+    fn check_bitslice_with_header(bits: usize, header_size: usize) {
+        let bytes = (mem::size_of::<usize>() * bits / 8) + header_size;
+        let tempfile = create_temp_mmap_file(bytes);
+
+        // Fill bitslice
+        {
+            let mut rng = StdRng::seed_from_u64(42);
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap_bitslice = MmapBitSlice::from(mmap, header_size);
+            (0..bits).for_each(|i| mmap_bitslice.set(i, rng.gen()));
+        }
+
+        // Reopen and assert contents
+        {
+            let mut rng = StdRng::seed_from_u64(42);
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mmap_bitslice = MmapBitSlice::from(mmap, header_size);
+            (0..bits).for_each(|i| assert_eq!(mmap_bitslice[i], rng.gen::<bool>()));
+        }
+    }
+
+    #[test]
+    fn test_bitslice_ones() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let tempfile = create_temp_mmap_file(64 * mem::size_of::<usize>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        assert_eq!(mmap_bitslice.iter_ones().next(), None);
+        assert_eq!(mmap_bitslice.count_ones(), 0);
+
+        for density in [0.01, 0.5, 1.0] {
+            for i in 0..mmap_bitslice.len() {
+                mmap_bitslice.set(i, rng.gen_bool(density));
+            }
+
+            let expected: Vec<usize> = (0..mmap_bitslice.len())
+                .filter(|&i| mmap_bitslice[i])
+                .collect();
+            assert_eq!(mmap_bitslice.iter_ones().collect::<Vec<_>>(), expected);
+            assert_eq!(mmap_bitslice.count_ones(), expected.len());
+        }
+    }
+
+    #[test]
+    fn test_bitslice_first() {
+        let tempfile = create_temp_mmap_file(0);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        assert_eq!(mmap_bitslice.first_one(), None);
+        assert_eq!(mmap_bitslice.first_zero(), None);
 
-    // Assert size
-    if mmap.len() != size_t {
-        return Err(Error::SizeExact(size_t, mmap.len()));
+        let tempfile = create_temp_mmap_file(4 * mem::size_of::<usize>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        let bits = mmap_bitslice.len();
+
+        // All clear
+        assert_eq!(mmap_bitslice.first_one(), None);
+        assert_eq!(mmap_bitslice.first_zero(), Some(0));
+
+        mmap_bitslice.set(bits - 3, true);
+        assert_eq!(mmap_bitslice.first_one(), Some(bits - 3));
+
+        // All set
+        for index in 0..bits {
+            mmap_bitslice.set(index, true);
+        }
+        assert_eq!(mmap_bitslice.first_one(), Some(0));
+        assert_eq!(mmap_bitslice.first_zero(), None);
+
+        mmap_bitslice.set(70, false);
+        mmap_bitslice.set(bits - 1, false);
+        assert_eq!(mmap_bitslice.first_zero(), Some(70));
+        assert_eq!(
+            mmap_bitslice.first_zero(),
+            mmap_bitslice.deref().first_zero()
+        );
     }
 
-    // Empty mmap is not supported on Windows, return zero-sized T at dangling pointer instead
-    #[cfg(windows)]
-    if mmap.is_empty() {
-        debug_assert_eq!(size_t, 0);
-        return Ok(NonNull::dangling().as_mut());
+    #[test]
+    fn test_bitslice_set_range() {
+        let tempfile = create_temp_mmap_file(4 * mem::size_of::<usize>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        let bits = mmap_bitslice.len();
+        let mut expected = vec![false; bits];
+
+        // Ranges within a single element, spanning elements and starting or ending mid element
+        let ranges = [
+            (3..9, true),
+            (60..70, true),
+            (5..5, false),
+            (100..bits - 7, true),
+            (64..128, false),
+            (0..bits, true),
+            (1..bits - 1, false),
+            (130..131, true),
+            (0..bits, false),
+            (63..65, true),
+        ];
+        for (range, value) in ranges {
+            mmap_bitslice.set_range(range.clone(), value);
+            expected[range].fill(value);
+            assert!(mmap_bitslice.iter().by_vals().eq(expected.iter().copied()));
+            assert_eq!(
+                mmap_bitslice.count_ones(),
+                expected.iter().filter(|&&bit| bit).count(),
+            );
+        }
     }
 
-    // Obtain unbounded bytes slice into mmap
-    let bytes: &'unbnd mut [u8] = {
-        let slice = mmap.deref_mut();
-        slice::from_raw_parts_mut(slice.as_mut_ptr(), slice.len())
-    };
+    #[test]
+    #[should_panic]
+    fn test_bitslice_set_range_out_of_bounds() {
+        let tempfile = create_temp_mmap_file(mem::size_of::<usize>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        let bits = mmap_bitslice.len();
+        mmap_bitslice.set_range(1..bits + 1, true);
+    }
 
-    // Assert alignment and size
-    assert_alignment::<_, T>(bytes);
-    debug_assert_eq!(mmap.len(), bytes.len());
-    if bytes.len() != mem::size_of::<T>() {
-        return Err(Error::SizeExact(mem::size_of::<T>(), bytes.len()));
+    #[test]
+    fn test_bitslice_set_atomic() {
+        let threads = 8;
+        let tempfile = create_temp_mmap_file(64 * mem::size_of::<usize>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        let bits = mmap_bitslice.len();
+
+        // Every thread sets its own disjoint bits, and all threads set and clear the same shared
+        // bits below 128 and at the end
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let mmap_bitslice = &mmap_bitslice;
+                scope.spawn(move || {
+                    // Safety: the bits are only accessed atomically until all threads are joined
+                    unsafe {
+                        for index in (128 + thread..bits - 1).step_by(threads) {
+                            mmap_bitslice.set_atomic(index, index % 3 == 0);
+                            assert_eq!(mmap_bitslice.get_atomic(index), index % 3 == 0);
+                        }
+                        for _ in 0..100 {
+                            for index in 0..128 {
+                                mmap_bitslice.set_atomic(index, true);
+                            }
+                            mmap_bitslice.set_atomic(bits - 1, false);
+                            assert!(mmap_bitslice.get_atomic(127));
+                        }
+                    }
+                });
+            }
+        });
+
+        let expected: Vec<usize> = (0..bits)
+            .filter(|&index| index < 128 || (index % 3 == 0 && index != bits - 1))
+            .collect();
+        assert_eq!(mmap_bitslice.iter_ones().collect::<Vec<_>>(), expected);
+        assert_eq!(mmap_bitslice.count_ones(), expected.len());
     }
 
-    let ptr = bytes.as_mut_ptr() as *mut T;
-    Ok(unsafe { &mut *ptr })
-}
+    #[test]
+    fn test_bitslice_count_cache() {
+        let tempfile = create_temp_mmap_file(4 * mem::size_of::<usize>());
 
-/// Get a second mutable reference for a slice of type `T` from the given mmap
-///
-/// A (non-zero) header size in bytes may be provided to omit from the BitSlice data.
-///
-/// On Windows, if an empty mmap is provided. An empty slice at dangling pointer is returned.
-///
-/// # Warning
-///
-/// The returned reference is unbounded. The user must ensure it never outlives the `mmap` type.
-///
-/// # Safety
-///
-/// - unsafe because we create a second (unbounded) mutable reference
-/// - malformed data in the mmap may break the transmuted slice for type `T` resulting in undefined
-///   behavior
-///
-/// # Panics
-///
-/// - panics when the mmap data is not correctly aligned for type `T`
-/// - panics when the header size isn't a multiple of size `T`
-unsafe fn mmap_to_slice_unbounded<'unbnd, T>(
-    mmap: &mut MmapMut,
-    header_size: usize,
-) -> Result<&'unbnd mut [T]>
-where
-    T: Sized,
-{
-    let size_t = mem::size_of::<T>();
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap_bitslice = MmapBitSlice::from(mmap, 0);
+            mmap_bitslice.set(3, true);
+            mmap_bitslice.set(3, true);
+            mmap_bitslice.set(100, true);
+            mmap_bitslice.set(200, true);
+            mmap_bitslice.set(200, false);
+            mmap_bitslice.set(201, false);
+            assert_eq!(mmap_bitslice.count_ones(), 2);
+
+            // Modifying through deref is not tracked until recomputing
+            mmap_bitslice.deref_mut().fill(true);
+            assert_eq!(mmap_bitslice.count_ones(), 2);
+            mmap_bitslice.recompute_count();
+            assert_eq!(mmap_bitslice.count_ones(), mmap_bitslice.len());
+            mmap_bitslice.set(0, false);
+            mmap_bitslice.flusher()().unwrap();
+        }
 
-    // Assert size
-    if size_t == 0 {
-        // For zero-sized T, data part must be zero-sized as well, we cannot have infinite slice
-        debug_assert_eq!(
-            mmap.len().saturating_sub(header_size),
-            0,
-            "mmap data must be zero-sized, because size T is zero",
-        );
-    } else {
-        // Must be multiple of size T
-        debug_assert_eq!(header_size % size_t, 0, "header not multiple of size T");
-        if mmap.len() % size_t != 0 {
-            return Err(Error::SizeMultiple(size_t, mmap.len()));
+        // Count is initialized when opening
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        assert_eq!(mmap_bitslice.count_ones(), mmap_bitslice.len() - 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_prefetch_and_lock() {
+        let tempfile = create_temp_mmap_file(4 * POPULATE_STRIDE);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u8> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.prefetch_and_lock().unwrap();
+        #[cfg(target_os = "linux")]
+        assert_eq!(mmap_slice.resident_bytes().unwrap(), 4 * POPULATE_STRIDE);
+        mmap_slice.unlock().unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_lock() {
+        let page_size = mmap_ops::page_size();
+        let tempfile = create_temp_mmap_file(page_size);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        mmap_bitslice.lock().unwrap();
+        mmap_bitslice.unlock().unwrap();
+        mmap_bitslice.lock().unwrap();
+
+        // Locked pages are resident, and accounted as locked memory of the process
+        #[cfg(target_os = "linux")]
+        {
+            let status = std::fs::read_to_string("/proc/self/status").unwrap();
+            let locked_kb: usize = status
+                .lines()
+                .find_map(|line| line.strip_prefix("VmLck:"))
+                .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+                .unwrap();
+            assert!(
+                locked_kb >= page_size / 1024,
+                "expected locked pages, got {locked_kb} kB",
+            );
         }
+
+        let mmap = mmap_ops::open_read_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapTypeReadOnly<[u8]> = unsafe { MmapTypeReadOnly::slice_from(mmap) };
+        mmap_slice.lock().unwrap();
+        mmap_slice.unlock().unwrap();
     }
 
-    // Empty mmap is not supported on Windows, return empty slice at dangling pointer instead
-    #[cfg(windows)]
-    if mmap.is_empty() {
-        let dangling = NonNull::dangling();
-        return Ok(slice::from_raw_parts_mut(dangling.as_ptr(), 0));
+    #[test]
+    fn test_madvise() {
+        let tempfile = create_temp_mmap_file(mmap_ops::page_size());
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.madvise(Advice::Sequential).unwrap();
+        mmap_slice.madvise(Advice::Random).unwrap();
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        mmap_bitslice.madvise(Advice::Normal).unwrap();
+
+        let mmap = mmap_ops::open_read_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapTypeReadOnly<[u8]> = unsafe { MmapTypeReadOnly::slice_from(mmap) };
+        mmap_slice.madvise(Advice::Sequential).unwrap();
     }
 
-    // Obtain unbounded bytes slice into mmap
-    let bytes: &'unbnd mut [u8] = {
-        let slice = mmap.deref_mut();
-        &mut slice::from_raw_parts_mut(slice.as_mut_ptr(), slice.len())[header_size..]
-    };
+    #[test]
+    fn test_madvise_discard() {
+        let tempfile = create_temp_mmap_file(mmap_ops::page_size());
 
-    // Assert alignment and bytes size
-    assert_alignment::<_, T>(bytes);
-    debug_assert_eq!(bytes.len() + header_size, mmap.len());
+        // Only given through the unsafe function
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice[0] = 42;
+        for advice in [Advice::DontNeed, Advice::Free] {
+            let err = mmap_slice.madvise(advice).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{advice:?}");
+            assert!(mmap_slice.set_advice(advice).is_err());
+        }
 
-    // Transmute slice types
-    Ok(slice::from_raw_parts_mut(
-        bytes.as_mut_ptr() as *mut T,
-        bytes.len().checked_div(size_t).unwrap_or(0),
-    ))
-}
+        // Pages of a shared file mapping are read back from the file
+        unsafe { mmap_slice.madvise_discard(Advice::DontNeed).unwrap() };
+        assert_eq!(mmap_slice[0], 42);
+        drop(mmap_slice);
+
+        // Changes of a copy-on-write mapping would be lost
+        let mut mmap_type = unsafe { MmapType::<u64>::from_cow(tempfile.path()).unwrap() };
+        *mmap_type = 7;
+        for advice in [Advice::DontNeed, Advice::Free] {
+            let err = unsafe { mmap_type.madvise_discard(advice) }.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{advice:?}");
+        }
+        unsafe { mmap_type.madvise_discard(Advice::Random).unwrap() };
+        assert_eq!(*mmap_type, 7);
+    }
 
-/// Assert slice `&[S]` is correctly aligned for type `T`.
-///
-/// # Panics
-///
-/// Panics when alignment is wrong.
-fn assert_alignment<S, T>(bytes: &[S]) {
-    assert_eq!(
-        bytes.as_ptr().align_offset(mem::align_of::<T>()),
-        0,
-        "type must be aligned",
-    );
-}
+    #[test]
+    fn test_page_size() {
+        let page_size = mmap_ops::page_size();
+        assert!(page_size.is_power_of_two());
+        assert_eq!(mapping_granularity() % page_size, 0);
+    }
 
-#[cfg(test)]
-mod tests {
-    use std::fmt::Debug;
-    use std::iter;
+    #[test]
+    fn test_open_range() {
+        let granularity = mapping_granularity();
+        let len = granularity / mem::size_of::<u32>();
+        let tempfile = create_temp_mmap_file(2 * granularity);
 
-    use rand::rngs::StdRng;
-    use rand::{Rng, SeedableRng};
-    use tempfile::{Builder, NamedTempFile};
+        // Write two arrays back to back
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap_slice: MmapSlice<u32> = unsafe { MmapSlice::from(mmap) };
+            mmap_slice[..len].fill(1);
+            mmap_slice[len..].fill(2);
+        }
 
-    use super::*;
-    use crate::common::mmap_ops;
+        let second: MmapSlice<u32> =
+            unsafe { MmapSlice::open_range(tempfile.path(), granularity, len).unwrap() };
+        assert_eq!(second.len(), len);
+        assert!(second.iter().all(|&value| value == 2));
 
-    fn create_temp_mmap_file(len: usize) -> NamedTempFile {
-        let tempfile = Builder::new()
-            .prefix("test.")
-            .suffix(".mmap")
-            .tempfile()
-            .unwrap();
-        tempfile.as_file().set_len(len as u64).unwrap();
-        tempfile
+        let result = unsafe { MmapSlice::<u32>::open_range(tempfile.path(), 4, 1) };
+        assert!(result.is_err());
+        let result = unsafe { MmapSlice::<u32>::open_range(tempfile.path(), granularity, len + 1) };
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_open_zero_type() {
-        check_open_zero_type::<()>(());
-        check_open_zero_type::<u8>(0);
-        check_open_zero_type::<usize>(0);
-        check_open_zero_type::<f32>(0.0);
+    fn test_try_into_pod() {
+        let tempfile = create_temp_mmap_file(4 * mem::size_of::<u32>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u32> = mmap.try_into().unwrap();
+        assert_eq!(mmap_slice.as_ref(), &[0; 4]);
+        drop(mmap_slice);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let result: Result<MmapSlice<[u8; 3]>> = mmap.try_into();
+        assert!(matches!(result, Err(Error::SizeMultiple(3, 16))));
+
+        // Resolves to the safe `TryFrom` implementation, no `unsafe` needed
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice = MmapSlice::<u32>::try_from(mmap).unwrap();
+        assert_eq!(mmap_slice.len(), 4);
     }
 
-    fn check_open_zero_type<T: Sized + PartialEq + Debug + 'static>(zero: T) {
-        let bytes = mem::size_of::<T>();
-        let tempfile = create_temp_mmap_file(bytes);
+    #[test]
+    fn test_byte_len_capacity() {
+        let tempfile = create_temp_mmap_file(5 * mem::size_of::<u64>());
         let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u64> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice.byte_len(), 40);
+        assert_eq!(mmap_slice.capacity(), 5);
 
-        let mmap_type: MmapType<T> = unsafe { MmapType::from(mmap) };
-        assert_eq!(mmap_type.deref(), &zero);
+        // Includes the checksum header
+        let tempfile = create_temp_mmap_file(CHECKSUM_HEADER_SIZE + 8);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapType<u64> = unsafe { MmapType::new_with_checksum(mmap, 1).unwrap() };
+        assert_eq!(mmap_type.byte_len(), CHECKSUM_HEADER_SIZE + 8);
     }
 
     #[test]
-    fn test_open_zero_slice() {
-        check_open_zero_slice::<()>(0, ());
-        check_open_zero_slice::<u8>(0, 0);
-        check_open_zero_slice::<u8>(1, 0);
-        check_open_zero_slice::<u8>(131, 0);
-        check_open_zero_slice::<usize>(0, 0);
-        check_open_zero_slice::<usize>(1, 0);
-        check_open_zero_slice::<usize>(131, 0);
-        check_open_zero_slice::<f32>(0, 0.0);
-        check_open_zero_slice::<f32>(1, 0.0);
-        check_open_zero_slice::<f32>(131, 0.0);
+    fn test_fill() {
+        let dir = Builder::new().prefix("mmap_fill").tempdir().unwrap();
+        let path = dir.path().join("test.mmap");
+
+        let mut mmap_slice: MmapSlice<u32> = unsafe { MmapSlice::create(&path, 5).unwrap() };
+        mmap_slice.fill(u32::MAX);
+        mmap_slice.flusher()().unwrap();
+        drop(mmap_slice);
+
+        let mmap = mmap_ops::open_write_mmap(&path).unwrap();
+        let mmap_slice: MmapSlice<u32> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice.as_ref(), &[u32::MAX; 5]);
     }
 
     #[test]
-    #[should_panic]
-    fn test_open_zero_slice_infinite_length() {
-        // A slice with zero-sized type T can never be more than 0 bytes
-        check_open_zero_slice::<()>(1, ());
+    fn test_open_with_advice() {
+        let dir = Builder::new().prefix("mmap_advice").tempdir().unwrap();
+        let path = dir.path().join("test.mmap");
+
+        let mut mmap_slice: MmapSlice<u32> =
+            unsafe { MmapSlice::create_with_advice(&path, 4, Advice::Sequential).unwrap() };
+        mmap_slice.copy_from_slice(&[1, 2, 3, 4]);
+        drop(mmap_slice);
+
+        let mmap = mmap_ops::open_write_mmap(&path).unwrap();
+        let mut mmap_slice: MmapSlice<u32> =
+            unsafe { MmapSlice::try_from_with_advice(mmap, Advice::Random).unwrap() };
+        assert_eq!(mmap_slice.as_ref(), &[1, 2, 3, 4]);
+        assert!(matches!(mmap_slice.advice(), Advice::Random));
+
+        // Advice is kept when remapping
+        mmap_slice.set_advice(Advice::Sequential).unwrap();
+        unsafe { mmap_slice.resize(&path, 8).unwrap() };
+        assert!(matches!(mmap_slice.advice(), Advice::Sequential));
     }
 
-    fn check_open_zero_slice<T: Sized + PartialEq + Debug + 'static>(len: usize, zero: T) {
-        let bytes = mem::size_of::<T>() * len;
-        let tempfile = create_temp_mmap_file(bytes);
+    #[test]
+    fn test_populate() {
+        let tempfile = create_temp_mmap_file(3 * POPULATE_STRIDE + 8);
+
         let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u8> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.populate();
+        mmap_slice.populate();
 
-        let mmap_slice: MmapSlice<T> = unsafe { MmapSlice::from(mmap) };
-        assert_eq!(mmap_slice.len(), len);
-        assert!(mmap_slice.iter().all(|i| i == &zero));
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        mmap_bitslice.populate();
+
+        let mmap = mmap_ops::open_read_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapTypeReadOnly<[u8]> = unsafe { MmapTypeReadOnly::slice_from(mmap) };
+        mmap_slice.populate();
+
+        // Empty mmaps have nothing to populate
+        let tempfile = create_temp_mmap_file(0);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u8> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.populate();
     }
 
     #[test]
-    fn test_reopen_random() {
-        let mut rng = StdRng::seed_from_u64(42);
-        check_reopen_random::<(), _>(0, || rng.gen());
-        check_reopen_random::<u8, _>(0, || rng.gen());
-        check_reopen_random::<u8, _>(1, || rng.gen());
-        check_reopen_random::<u8, _>(131, || rng.gen());
-        check_reopen_random::<usize, _>(0, || rng.gen());
-        check_reopen_random::<usize, _>(1, || rng.gen());
-        check_reopen_random::<usize, _>(131, || rng.gen());
-        check_reopen_random::<f32, _>(0, || rng.gen());
-        check_reopen_random::<f32, _>(1, || rng.gen());
-        check_reopen_random::<f32, _>(131, || rng.gen());
-    }
+    #[cfg(target_os = "linux")]
+    fn test_resident_bytes() {
+        let len = 3 * POPULATE_STRIDE;
+        let tempfile = create_temp_mmap_file(len);
 
-    fn check_reopen_random<T, R>(len: usize, rng: R)
-    where
-        T: Sized + Copy + PartialEq + Debug + 'static,
-        R: FnMut() -> T,
-    {
-        let bytes = mem::size_of::<T>() * len;
-        let tempfile = create_temp_mmap_file(bytes);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u8> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.populate();
+        assert_eq!(mmap_slice.resident_bytes().unwrap(), len);
 
-        let template: Vec<T> = iter::repeat_with(rng).take(len).collect();
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_bitslice = MmapBitSlice::from(mmap, 0);
+        mmap_bitslice.populate();
+        assert_eq!(mmap_bitslice.resident_bytes().unwrap(), len);
 
-        // Write random values from template into mmap
-        {
-            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
-            let mut mmap_slice: MmapSlice<T> = unsafe { MmapSlice::from(mmap) };
-            assert_eq!(mmap_slice.len(), len);
-            mmap_slice.copy_from_slice(&template);
-        }
+        let tempfile = create_temp_mmap_file(0);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u8> = unsafe { MmapSlice::from(mmap) };
+        assert_eq!(mmap_slice.resident_bytes().unwrap(), 0);
+    }
 
-        // Reopen and assert values from template
-        {
-            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
-            let mmap_slice: MmapSlice<T> = unsafe { MmapSlice::from(mmap) };
-            assert_eq!(mmap_slice.as_ref(), template);
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_populate_read() {
+        let len = 3 * POPULATE_STRIDE;
+        let tempfile = create_temp_mmap_file(len);
+
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u8> = unsafe { MmapSlice::from(mmap) };
+        match mmap_slice.madvise(Advice::PopulateRead) {
+            Ok(()) => {}
+            // `MADV_POPULATE_READ` is not supported before Linux 5.14
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+                eprintln!("Skip test, populate advice not supported by this kernel");
+                return;
+            }
+            Err(err) => panic!("failed to populate: {err}"),
         }
+        assert_eq!(mmap_slice.resident_bytes().unwrap(), len);
+
+        let tempfile = create_temp_mmap_file(len);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_slice: MmapSlice<u8> = unsafe { MmapSlice::from(mmap) };
+        mmap_slice.madvise(Advice::PopulateWrite).unwrap();
+        assert_eq!(mmap_slice.resident_bytes().unwrap(), len);
     }
 
     #[test]
-    fn test_bitslice() {
-        check_bitslice_with_header(0, 0);
-        check_bitslice_with_header(0, 128);
-        check_bitslice_with_header(512, 0);
-        check_bitslice_with_header(512, 256);
-        check_bitslice_with_header(11721 * 8, 256);
+    fn test_invalid_mmap() {
+        let open = |len| {
+            let tempfile = create_temp_mmap_file(len);
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            (tempfile, mmap)
+        };
+
+        let (_file, mmap) = open(7);
+        let result = unsafe { MmapType::<u64>::try_from(mmap) };
+        assert!(matches!(result, Err(Error::SizeExact(8, 7))));
+
+        let (_file, mmap) = open(12);
+        let result = unsafe { MmapSlice::<u64>::try_from_unchecked(mmap) };
+        assert!(matches!(result, Err(Error::SizeMultiple(8, 12))));
+
+        let (_file, mmap) = open(1);
+        let result = unsafe { MmapSlice::<()>::try_from_unchecked(mmap) };
+        assert!(matches!(result, Err(Error::SizeExact(0, 1))));
+
+        let (_file, mmap) = open(64);
+        let result = MmapBitSlice::try_from(mmap, 4);
+        assert!(matches!(
+            result,
+            Err(Error::HeaderSizeMultiple(size, 4)) if size == mem::size_of::<usize>(),
+        ));
+
+        let (_file, mmap) = open(64);
+        let result = MmapBitSlice::try_from(mmap, 128);
+        assert!(matches!(result, Err(Error::HeaderSizeExceeded(128, 64))));
+
+        let bytes = [0u8; 16];
+        let align = mem::align_of::<u64>();
+        let misaligned = &bytes[bytes.as_ptr().align_offset(align) + 1..];
+        assert!(matches!(
+            check_alignment::<_, u64>(misaligned),
+            Err(Error::Alignment(a)) if a == align,
+        ));
     }
 
-    fn check_bitslice_with_header(bits: usize, header_size: usize) {
-        let bytes = (mem::size_of::<usize>() * bits / 8) + header_size;
-        let tempfile = create_temp_mmap_file(bytes);
+    #[test]
+    fn test_checksum() {
+        let tempfile = create_temp_mmap_file(CHECKSUM_HEADER_SIZE + mem::size_of::<[u64; 4]>());
 
-        // Fill bitslice
+        // Write values with checksum, and update checksum after modifying them
         {
-            let mut rng = StdRng::seed_from_u64(42);
             let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
-            let mut mmap_bitslice = MmapBitSlice::from(mmap, header_size);
-            (0..bits).for_each(|i| mmap_bitslice.set(i, rng.gen()));
+            let mut mmap_type =
+                unsafe { MmapType::new_with_checksum(mmap, [1u64, 2, 3, 4]).unwrap() };
+            assert!(mmap_type.verify_checksum());
+            mmap_type[0] = 5;
+            assert!(!mmap_type.verify_checksum());
+            mmap_type.update_checksum();
+            assert!(mmap_type.verify_checksum());
+            mmap_type.flusher()().unwrap();
         }
 
-        // Reopen and assert contents
+        // Reopen and assert values
         {
-            let mut rng = StdRng::seed_from_u64(42);
             let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
-            let mmap_bitslice = MmapBitSlice::from(mmap, header_size);
-            (0..bits).for_each(|i| assert_eq!(mmap_bitslice[i], rng.gen::<bool>()));
+            let mmap_type = unsafe { MmapType::<[u64; 4]>::try_from_with_checksum(mmap).unwrap() };
+            assert_eq!(mmap_type.deref(), &[5, 2, 3, 4]);
+        }
+
+        // Corrupt data without updating the checksum, reopening must fail
+        {
+            let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+            let mut mmap_type: MmapType<[u64; 5]> = unsafe { MmapType::from(mmap) };
+            mmap_type[2] = 6;
+            mmap_type.flusher()().unwrap();
         }
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let result = unsafe { MmapType::<[u64; 4]>::try_from_with_checksum(mmap) };
+        assert!(matches!(result, Err(Error::ChecksumMismatch(_, _))));
+
+        // Types without a checksum header always verify
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapType<[u64; 5]> = unsafe { MmapType::from(mmap) };
+        assert!(mmap_type.verify_checksum());
+
+        let tempfile = create_temp_mmap_file(4);
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let result = unsafe { MmapType::<u64>::try_from_with_checksum(mmap) };
+        assert!(matches!(result, Err(Error::HeaderSizeExceeded(8, 4))));
+    }
+
+    #[test]
+    fn test_scrub() {
+        let tempfile = create_temp_mmap_file(CHECKSUM_HEADER_SIZE + mem::size_of::<[u64; 4]>());
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mut mmap_type = unsafe { MmapType::new_with_checksum(mmap, [1u64, 2, 3, 4]).unwrap() };
+
+        // Scrub in steps of 12 bytes
+        assert_eq!(
+            mmap_type.scrub(12),
+            ScrubProgress::InProgress {
+                offset: 12,
+                len: 32
+            },
+        );
+        assert_eq!(
+            mmap_type.scrub(12),
+            ScrubProgress::InProgress {
+                offset: 24,
+                len: 32
+            },
+        );
+        assert_eq!(mmap_type.scrub(12), ScrubProgress::Verified);
+
+        // Next call starts a new pass
+        assert_eq!(mmap_type.scrub(usize::MAX), ScrubProgress::Verified);
+
+        // Updating the checksum halfway restarts the pass
+        assert!(matches!(
+            mmap_type.scrub(16),
+            ScrubProgress::InProgress { offset: 16, .. },
+        ));
+        mmap_type[0] = 5;
+        mmap_type.update_checksum();
+        assert!(matches!(
+            mmap_type.scrub(16),
+            ScrubProgress::InProgress { offset: 16, .. },
+        ));
+        assert_eq!(mmap_type.scrub(16), ScrubProgress::Verified);
+
+        // Corrupt data without updating the checksum
+        mmap_type[3] = 6;
+        match mmap_type.scrub(usize::MAX) {
+            ScrubProgress::Corrupted { stored, computed } => assert_ne!(stored, computed),
+            progress => panic!("unexpected scrub progress {progress:?}"),
+        }
+
+        // Types without a checksum header have nothing to scrub
+        let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
+        let mmap_type: MmapType<[u64; 5]> = unsafe { MmapType::from(mmap) };
+        assert_eq!(mmap_type.scrub(usize::MAX), ScrubProgress::NoChecksum);
     }
 
     #[test]
@@ -639,9 +3559,9 @@ mod tests {
         {
             let tempfile = create_temp_mmap_file(0);
             let mmap = mmap_ops::open_write_mmap(tempfile.path()).unwrap();
-            let result = unsafe { MmapSlice::<()>::try_from(mmap).unwrap() };
+            let result = unsafe { MmapSlice::<()>::try_from_unchecked(mmap).unwrap() };
             assert_eq!(result.as_ref(), &[]);
-            assert_alignment::<_, ()>(result.as_ref());
+            check_alignment::<_, ()>(result.as_ref()).unwrap();
         }
     }
 }
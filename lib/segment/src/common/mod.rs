@@ -16,6 +16,26 @@ use crate::types::SegmentConfig;
 
 pub type Flusher = Box<dyn FnOnce() -> OperationResult<()> + Send>;
 
+/// Combine flushers into a single one, which runs all of them in order
+///
+/// A failing flusher does not prevent the remaining ones from running. The first error is
+/// returned, later errors are logged.
+pub fn combine_flushers(flushers: Vec<Flusher>) -> Flusher {
+    Box::new(move || {
+        let mut result = Ok(());
+        for flusher in flushers {
+            if let Err(err) = flusher() {
+                if result.is_ok() {
+                    result = Err(err);
+                } else {
+                    log::error!("Failed to flush: {err}");
+                }
+            }
+        }
+        result
+    })
+}
+
 pub fn check_vector_name(vector_name: &str, segment_config: &SegmentConfig) -> OperationResult<()> {
     if !segment_config.vector_data.contains_key(vector_name) {
         return Err(OperationError::VectorNameNotExists {
@@ -34,3 +54,39 @@ pub fn check_vectors_set(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_combine_flushers() {
+        let flushed = Arc::new(AtomicUsize::new(0));
+        let flusher = |result: OperationResult<()>| -> Flusher {
+            let flushed = flushed.clone();
+            Box::new(move || {
+                flushed.fetch_add(1, Ordering::Relaxed);
+                result
+            })
+        };
+
+        let combined = combine_flushers(vec![
+            flusher(Ok(())),
+            flusher(Err(OperationError::service_error("first"))),
+            flusher(Err(OperationError::service_error("second"))),
+            flusher(Ok(())),
+        ]);
+        match combined() {
+            Err(OperationError::ServiceError { description, .. }) => {
+                assert_eq!(description, "first")
+            }
+            other => panic!("unexpected flush result: {other:?}"),
+        }
+        assert_eq!(flushed.load(Ordering::Relaxed), 4);
+
+        assert!(combine_flushers(vec![])().is_ok());
+    }
+}
@@ -1,15 +1,94 @@
 //! Platform-independent abstractions over [`memmap2::Mmap::advise`]/[`memmap2::MmapMut::advise`]
 //! and [`memmap2::Advice`].
 
+use std::fmt;
 use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Weak};
+use std::time::Instant;
 
-use serde::Deserialize;
+use serde::de::value::Error as ValueError;
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
 
 /// Global [`Advice`] value, to trivially set [`Advice`] value
 /// used by all memmaps created by the [`segment`] crate.
 ///
-/// See [`store_global`] and [`load_global`].
-static ADVICE: parking_lot::RwLock<Advice> = parking_lot::RwLock::new(Advice::Random);
+/// See [`store_global`] and [`load_global`]. Initialized to the default, see [`Advice::default`].
+static ADVICE: parking_lot::RwLock<GlobalAdvice> = parking_lot::RwLock::new(GlobalAdvice {
+    advice: Advice::Random,
+    changed_at: None,
+});
+
+/// Snapshot of the global [`Advice`] value, see [`get_global_info`]
+#[derive(Copy, Clone, Debug)]
+pub struct GlobalAdvice {
+    /// Current global [`Advice`] value
+    pub advice: Advice,
+    /// When the global [`Advice`] value was last set, or `None` if it was never set
+    pub changed_at: Option<Instant>,
+}
+
+/// Memory maps to advise again when the global [`Advice`] value changes.
+///
+/// See [`register`].
+static REGISTRY: AdviceRegistry = AdviceRegistry::new();
+
+/// Memory maps to advise again at once, such as when the global [`Advice`] value changes
+///
+/// Only weak references are kept, so registering does not keep a memory map alive. The global
+/// registry is used through [`register`], [`unregister`] and [`set_global`].
+#[derive(Default)]
+pub struct AdviceRegistry {
+    madviseables: parking_lot::Mutex<Vec<Weak<dyn Madviseable + Send + Sync>>>,
+}
+
+impl AdviceRegistry {
+    pub const fn new() -> Self {
+        Self {
+            madviseables: parking_lot::Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Register memory map to advise again on [`AdviceRegistry::advise`].
+    pub fn register<M>(&self, madviseable: &Arc<M>)
+    where
+        M: Madviseable + Send + Sync + 'static,
+    {
+        let madviseable: Arc<dyn Madviseable + Send + Sync> = madviseable.clone();
+        let mut madviseables = self.madviseables.lock();
+        madviseables.retain(|madviseable| madviseable.strong_count() > 0);
+        madviseables.push(Arc::downgrade(&madviseable));
+    }
+
+    /// Unregister memory map, so it is not advised again.
+    pub fn unregister<M>(&self, madviseable: &Arc<M>)
+    where
+        M: Madviseable + Send + Sync + 'static,
+    {
+        let ptr = Arc::as_ptr(madviseable).cast::<()>();
+        self.madviseables
+            .lock()
+            .retain(|registered| registered.as_ptr().cast::<()>() != ptr);
+    }
+
+    /// Advise all registered memory maps, and forget the ones that are dropped.
+    ///
+    /// Failures are logged, and don't prevent advising the other memory maps.
+    pub fn advise(&self, advice: Advice) {
+        self.madviseables
+            .lock()
+            .retain(|madviseable| match madviseable.upgrade() {
+                Some(madviseable) => {
+                    if let Err(err) = madviseable.madvise(advice) {
+                        log::warn!("Failed to advise {advice:?} for registered mmap: {err}");
+                    }
+                    true
+                }
+                None => false,
+            });
+    }
+}
 
 /// Set global [`Advice`] value.
 ///
@@ -19,17 +98,54 @@ static ADVICE: parking_lot::RwLock<Advice> = parking_lot::RwLock::new(Advice::Ra
 /// (obtained with [`load_global`]).
 ///
 /// It is recommended to set the desired [`Advice`] value before calling any other function
-/// from the [`segment`] crate and not to change it afterwards.
+/// from the [`segment`] crate. Memory maps created before are only advised the new value if
+/// they are registered, see [`register`].
 ///
 /// The [`segment`] crate itself does not modify global [`Advice`] value.
 ///
 /// Default global [`Advice`] value is [`Advice::Random`].
-pub fn set_global(advice: Advice) {
-    *ADVICE.write() = advice;
+///
+/// Returns an error for advice that may discard data, see [`Advice::discards_data`], which must
+/// never be applied to all memory maps. The global advice is not changed then.
+pub fn set_global(advice: Advice) -> io::Result<()> {
+    if advice.discards_data() {
+        return Err(discard_error(advice));
+    }
+
+    *ADVICE.write() = GlobalAdvice {
+        advice,
+        changed_at: Some(Instant::now()),
+    };
+
+    REGISTRY.advise(advice);
+    Ok(())
+}
+
+/// Register memory map to advise again when the global [`Advice`] value changes.
+///
+/// Only a weak reference is kept, so registering does not keep the memory map alive.
+pub fn register<M>(madviseable: &Arc<M>)
+where
+    M: Madviseable + Send + Sync + 'static,
+{
+    REGISTRY.register(madviseable);
+}
+
+/// Unregister memory map, so it is not advised again when the global [`Advice`] value changes.
+pub fn unregister<M>(madviseable: &Arc<M>)
+where
+    M: Madviseable + Send + Sync + 'static,
+{
+    REGISTRY.unregister(madviseable);
 }
 
 /// Get current global [`Advice`] value.
 pub fn get_global() -> Advice {
+    ADVICE.read().advice
+}
+
+/// Get current global [`Advice`] value, and when it was last set.
+pub fn get_global_info() -> GlobalAdvice {
     *ADVICE.read()
 }
 
@@ -37,58 +153,844 @@ pub fn get_global() -> Advice {
 /// See [`memmap2::Advice`] and [madvise()] man page.
 ///
 /// [madvice()]: https://man7.org/linux/man-pages/man2/madvise.2.html
-#[derive(Copy, Clone, Debug, Deserialize)]
+///
+/// Defaults to [`Advice::Random`], like the global [`Advice`] value.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Advice {
     /// See [`memmap2::Advice::Normal`].
     Normal,
 
     /// See [`memmap2::Advice::Random`].
+    #[default]
     Random,
 
     /// See [`memmap2::Advice::Sequential`].
     Sequential,
+
+    /// See [`memmap2::Advice::WillNeed`].
+    ///
+    /// Prefetch pages ahead of time, for example before scanning a memory map. Also supported on
+    /// Windows, see `PrefetchVirtualMemory`.
+    WillNeed,
+
+    /// See [`memmap2::UncheckedAdvice::DontNeed`].
+    ///
+    /// Release memory of a memory map that is not accessed for a while, such as a cold storage.
+    /// On shared file backed memory maps pages are read from the file again on next access.
+    /// Discards the data of private memory maps, so only given through
+    /// [`Madviseable::madvise_discard_range`].
+    DontNeed,
+
+    /// See [`memmap2::UncheckedAdvice::Free`].
+    ///
+    /// Lazily reclaim pages of a private anonymous memory map, of which the data may be
+    /// discarded. Only supported on Linux, and only given through
+    /// [`Madviseable::madvise_discard_range`].
+    Free,
+
+    /// See [`memmap2::Advice::PopulateRead`].
+    ///
+    /// Populate page tables readable, prefaulting pages ahead of time. Only supported on Linux
+    /// 5.14 and later. Prefetches pages on Windows instead, see `PrefetchVirtualMemory`.
+    PopulateRead,
+
+    /// See [`memmap2::Advice::PopulateWrite`].
+    ///
+    /// Populate page tables writable, prefaulting pages ahead of time. For example for a newly
+    /// created memory map that is about to be written fully. Only supported on Linux 5.14 and
+    /// later, and only on writable memory maps.
+    PopulateWrite,
+}
+
+impl Advice {
+    /// Snake case name, as used in configuration
+    fn name(&self) -> &'static str {
+        match self {
+            Advice::Normal => "normal",
+            Advice::Random => "random",
+            Advice::Sequential => "sequential",
+            Advice::WillNeed => "will_need",
+            Advice::DontNeed => "dont_need",
+            Advice::Free => "free",
+            Advice::PopulateRead => "populate_read",
+            Advice::PopulateWrite => "populate_write",
+        }
+    }
+
+    /// One line explanation of the advice, for humans
+    pub fn description(&self) -> &'static str {
+        match self {
+            Advice::Normal => "no special access pattern",
+            Advice::Random => "expect random access, read ahead less",
+            Advice::Sequential => "expect sequential access, read ahead more",
+            Advice::WillNeed => "prefetch pages eagerly",
+            Advice::DontNeed => "release pages, read them again on next access",
+            Advice::Free => "lazily reclaim pages of private anonymous memory maps",
+            Advice::PopulateRead => "prefault pages readable",
+            Advice::PopulateWrite => "prefault pages writable",
+        }
+    }
+
+    /// Whether the advice may discard data written to a memory map
+    ///
+    /// Such advice is only given through [`Madviseable::madvise_discard_range`], which is
+    /// `unsafe`.
+    pub fn discards_data(&self) -> bool {
+        matches!(self, Advice::DontNeed | Advice::Free)
+    }
+}
+
+/// Snake case name of the advice, such as `random` or `populate_read`
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parse the snake case name of an advice, such as `random` or `populate_read`
+///
+/// Uses the same names as deserializing, so an environment variable or command line flag can be
+/// parsed like the configuration.
+impl FromStr for Advice {
+    type Err = ValueError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(name.into_deserializer())
+    }
 }
 
 #[cfg(unix)]
-impl From<Advice> for memmap2::Advice {
-    fn from(advice: Advice) -> Self {
-        match advice {
+impl TryFrom<Advice> for memmap2::Advice {
+    type Error = io::Error;
+
+    fn try_from(advice: Advice) -> io::Result<Self> {
+        let advice = match advice {
             Advice::Normal => memmap2::Advice::Normal,
             Advice::Random => memmap2::Advice::Random,
             Advice::Sequential => memmap2::Advice::Sequential,
-        }
+            Advice::WillNeed => memmap2::Advice::WillNeed,
+            #[cfg(target_os = "linux")]
+            Advice::PopulateRead => memmap2::Advice::PopulateRead,
+            #[cfg(target_os = "linux")]
+            Advice::PopulateWrite => memmap2::Advice::PopulateWrite,
+            // May discard data, given with `unchecked_advise_range` instead, see
+            // `unchecked_advice`
+            Advice::DontNeed => return Err(discard_error(advice)),
+            #[cfg(not(target_os = "linux"))]
+            Advice::Free | Advice::PopulateRead | Advice::PopulateWrite => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{advice:?} advice is only supported on Linux"),
+                ))
+            }
+            #[cfg(target_os = "linux")]
+            Advice::Free => return Err(discard_error(advice)),
+        };
+        Ok(advice)
+    }
+}
+
+/// Advice that may discard data of the memory map, which `memmap2` only gives as `unsafe`
+/// [`memmap2::UncheckedAdvice`]
+///
+/// Returns `None` for all other advice, which is converted to [`memmap2::Advice`] instead.
+#[cfg(unix)]
+fn unchecked_advice(advice: Advice) -> Option<memmap2::UncheckedAdvice> {
+    match advice {
+        Advice::DontNeed => Some(memmap2::UncheckedAdvice::DontNeed),
+        #[cfg(target_os = "linux")]
+        Advice::Free => Some(memmap2::UncheckedAdvice::Free),
+        _ => None,
     }
 }
 
+/// Error for advice that may discard data, given through the safe advise functions
+fn discard_error(advice: Advice) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{advice:?} advice may discard data, see `Madviseable::madvise_discard_range`"),
+    )
+}
+
 /// Advise OS how given memory map will be accessed. On non-Unix platforms this is a no-op.
 pub fn madvise(madviseable: &impl Madviseable, advice: Advice) -> io::Result<()> {
     madviseable.madvise(advice)
 }
 
+/// Advise OS how all given memory maps will be accessed.
+///
+/// A failure does not prevent advising the remaining memory maps. The first error is returned,
+/// later errors are logged.
+pub fn madvise_all(madviseables: &[&dyn Madviseable], advice: Advice) -> io::Result<()> {
+    let mut result = Ok(());
+    for madviseable in madviseables {
+        if let Err(err) = madviseable.madvise(advice) {
+            if result.is_ok() {
+                result = Err(err);
+            } else {
+                log::warn!("Failed to advise {advice:?} for mmap: {err}");
+            }
+        }
+    }
+    result
+}
+
+/// Advise OS how given memory map will be accessed, and log a warning if that fails.
+///
+/// Still returns the result, so callers that otherwise ignore it make failures visible.
+pub fn madvise_logged(madviseable: &impl Madviseable, advice: Advice) -> io::Result<()> {
+    let result = madviseable.madvise(advice);
+    if let Err(err) = &result {
+        log::warn!("Failed to advise {advice:?} for mmap: {err}");
+    }
+    result
+}
+
 /// Generic, platform-independent abstraction
 /// over [`memmap2::Mmap::advise`] and [`memmap2::MmapMut::advise`].
 pub trait Madviseable {
-    /// Advise OS how given memory map will be accessed. On non-Unix platforms this is a no-op.
+    /// Advise OS how given memory map will be accessed. On non-Unix platforms this is a no-op,
+    /// except for prefetching on Windows.
     fn madvise(&self, advice: Advice) -> io::Result<()>;
+
+    /// Advise OS how `len` bytes of given memory map, starting at byte `offset`, will be accessed.
+    /// On non-Unix platforms this is a no-op, except for prefetching on Windows.
+    ///
+    /// Returns an error if the range exceeds the memory map, and for advice that may discard
+    /// data, which is only given through [`Madviseable::madvise_discard_range`].
+    fn madvise_range(&self, offset: usize, len: usize, advice: Advice) -> io::Result<()>;
+
+    /// Advise OS how `len` bytes of given memory map, starting at byte `offset`, will be accessed,
+    /// including advice that may discard data, see [`Advice::discards_data`]. Other advice is
+    /// given as with [`Madviseable::madvise_range`].
+    ///
+    /// Returns an [`io::ErrorKind::Unsupported`] error for advice that may discard data, unless
+    /// implemented for the memory map.
+    ///
+    /// # Safety
+    ///
+    /// Data written to the range may be lost, also for file backed memory maps. The pages of
+    /// private (copy-on-write) and anonymous memory maps are discarded, and read back as the
+    /// file contents or as zeros. The caller must accept losing that data, and that values read
+    /// from the range change underneath any reference into it.
+    unsafe fn madvise_discard_range(
+        &self,
+        offset: usize,
+        len: usize,
+        advice: Advice,
+    ) -> io::Result<()> {
+        if advice.discards_data() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{advice:?} advice is not supported for this memory map"),
+            ));
+        }
+        self.madvise_range(offset, len, advice)
+    }
+
+    /// Advise OS how given memory map will be accessed, and report whether it had any effect.
+    ///
+    /// Returns [`AdviceOutcome::Unsupported`] for advice that is not supported on this platform,
+    /// instead of silently doing nothing or returning an error. See [`is_supported`].
+    fn madvise_applied(&self, advice: Advice) -> io::Result<AdviceOutcome> {
+        if !is_supported(advice) {
+            return Ok(AdviceOutcome::Unsupported);
+        }
+        self.madvise(advice)?;
+        Ok(AdviceOutcome::Applied)
+    }
+}
+
+/// Whether advice had any effect, see [`Madviseable::madvise_applied`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AdviceOutcome {
+    /// Advice was given to the OS
+    Applied,
+    /// Advice is not supported on this platform, and had no effect
+    Unsupported,
+}
+
+/// Whether given advice has any effect on this platform
+///
+/// All advice is supported on Linux, except [`Advice::PopulateRead`] and
+/// [`Advice::PopulateWrite`] before kernel 5.14. Their support is probed once by advising a small
+/// anonymous memory map, and cached. Other Unix platforms don't support [`Advice::Free`],
+/// [`Advice::PopulateRead`] and [`Advice::PopulateWrite`]. Windows only supports prefetching
+/// with [`Advice::WillNeed`] and [`Advice::PopulateRead`].
+pub fn is_supported(advice: Advice) -> bool {
+    if cfg!(target_os = "linux") {
+        !matches!(advice, Advice::PopulateRead | Advice::PopulateWrite) || populate_supported()
+    } else if cfg!(unix) {
+        !matches!(
+            advice,
+            Advice::Free | Advice::PopulateRead | Advice::PopulateWrite
+        )
+    } else if cfg!(windows) {
+        matches!(advice, Advice::WillNeed | Advice::PopulateRead)
+    } else {
+        false
+    }
+}
+
+/// Whether the kernel supports the populate advice, which it rejects with `EINVAL` before 5.14
+#[cfg(target_os = "linux")]
+fn populate_supported() -> bool {
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    const UNKNOWN: u8 = 0;
+    const SUPPORTED: u8 = 1;
+    const UNSUPPORTED: u8 = 2;
+    static POPULATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+    match POPULATE.load(Ordering::Relaxed) {
+        SUPPORTED => true,
+        UNSUPPORTED => false,
+        _ => {
+            let supported = memmap2::MmapMut::map_anon(crate::common::mmap_ops::page_size())
+                .and_then(|mmap| mmap.advise(memmap2::Advice::PopulateRead))
+                .is_ok();
+            let state = if supported { SUPPORTED } else { UNSUPPORTED };
+            POPULATE.store(state, Ordering::Relaxed);
+            supported
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn populate_supported() -> bool {
+    false
 }
 
 impl Madviseable for memmap2::Mmap {
     fn madvise(&self, advice: Advice) -> io::Result<()> {
+        self.madvise_range(0, self.len(), advice)
+    }
+
+    fn madvise_range(&self, offset: usize, len: usize, advice: Advice) -> io::Result<()> {
+        check_range(offset, len, self.len())?;
+        if advice.discards_data() {
+            return Err(discard_error(advice));
+        }
         #[cfg(unix)]
-        self.advise(advice.into())?;
+        self.advise_range(advice.try_into()?, offset, len)?;
+        #[cfg(windows)]
+        if let Advice::WillNeed | Advice::PopulateRead = advice {
+            return prefetch(&self[offset..offset + len]);
+        }
         #[cfg(not(unix))]
         log::debug!("Ignore {advice:?} on this platform");
         Ok(())
     }
+
+    unsafe fn madvise_discard_range(
+        &self,
+        offset: usize,
+        len: usize,
+        advice: Advice,
+    ) -> io::Result<()> {
+        check_range(offset, len, self.len())?;
+        #[cfg(unix)]
+        if let Some(advice) = unchecked_advice(advice) {
+            // Safety: the caller accepts losing the data of the range
+            return self.unchecked_advise_range(advice, offset, len);
+        }
+        if advice.discards_data() {
+            log::debug!("Ignore {advice:?} on this platform");
+            return Ok(());
+        }
+        self.madvise_range(offset, len, advice)
+    }
 }
 
 impl Madviseable for memmap2::MmapMut {
     fn madvise(&self, advice: Advice) -> io::Result<()> {
+        self.madvise_range(0, self.len(), advice)
+    }
+
+    fn madvise_range(&self, offset: usize, len: usize, advice: Advice) -> io::Result<()> {
+        check_range(offset, len, self.len())?;
+        if advice.discards_data() {
+            return Err(discard_error(advice));
+        }
         #[cfg(unix)]
-        self.advise(advice.into())?;
+        self.advise_range(advice.try_into()?, offset, len)?;
+        #[cfg(windows)]
+        if let Advice::WillNeed | Advice::PopulateRead = advice {
+            return prefetch(&self[offset..offset + len]);
+        }
         #[cfg(not(unix))]
         log::debug!("Ignore {advice:?} on this platform");
         Ok(())
     }
+
+    unsafe fn madvise_discard_range(
+        &self,
+        offset: usize,
+        len: usize,
+        advice: Advice,
+    ) -> io::Result<()> {
+        check_range(offset, len, self.len())?;
+        #[cfg(unix)]
+        if let Some(advice) = unchecked_advice(advice) {
+            // Safety: the caller accepts losing the data of the range
+            return self.unchecked_advise_range(advice, offset, len);
+        }
+        if advice.discards_data() {
+            log::debug!("Ignore {advice:?} on this platform");
+            return Ok(());
+        }
+        self.madvise_range(offset, len, advice)
+    }
+}
+
+/// Ignores all advice without touching the OS
+///
+/// For memory maps that shouldn't be advised, and for testing APIs taking a [`Madviseable`]
+/// without a real memory map.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoopMadvise;
+
+impl Madviseable for NoopMadvise {
+    fn madvise(&self, _advice: Advice) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn madvise_range(&self, _offset: usize, _len: usize, _advice: Advice) -> io::Result<()> {
+        Ok(())
+    }
+
+    unsafe fn madvise_discard_range(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _advice: Advice,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Default number of consecutive page accesses after which [`AdaptiveAdvisor`] detects a scan
+#[cfg(feature = "adaptive_advice")]
+pub const DEFAULT_SCAN_THRESHOLD: usize = 16;
+
+/// Advises a memory map based on how it is accessed
+///
+/// Starts with [`Advice::Normal`]. Switches to [`Advice::Sequential`] when it detects a scan,
+/// being [`AdaptiveAdvisor::scan_threshold`] consecutive page accesses, and to
+/// [`Advice::Random`] on any other jump. Advice is given for the range from the accessed page to
+/// the end of the memory map, and only when it changes.
+#[cfg(feature = "adaptive_advice")]
+pub struct AdaptiveAdvisor<'a, M: Madviseable> {
+    madviseable: &'a M,
+    /// Size in bytes of the memory map
+    len: usize,
+    /// Size in bytes of a page, see [`crate::common::mmap_ops::page_size`]
+    page_size: usize,
+    /// Number of consecutive page accesses after which access is considered a scan
+    pub scan_threshold: usize,
+    /// Last accessed page
+    last_page: Option<usize>,
+    /// Number of consecutive page accesses up to the last accessed page
+    consecutive: usize,
+    /// Advice currently given
+    advice: Advice,
+}
+
+#[cfg(feature = "adaptive_advice")]
+impl<'a, M: Madviseable> AdaptiveAdvisor<'a, M> {
+    /// Advise memory map of `len` bytes based on how it is accessed, see [`AdaptiveAdvisor`].
+    pub fn new(madviseable: &'a M, len: usize) -> Self {
+        Self {
+            madviseable,
+            len,
+            page_size: crate::common::mmap_ops::page_size(),
+            scan_threshold: DEFAULT_SCAN_THRESHOLD,
+            last_page: None,
+            consecutive: 0,
+            advice: Advice::Normal,
+        }
+    }
+
+    /// Advice currently given
+    pub fn advice(&self) -> Advice {
+        self.advice
+    }
+
+    /// Record an access at byte `offset`, and advise the memory map if the inferred access
+    /// pattern changed.
+    pub fn access(&mut self, offset: usize) -> io::Result<()> {
+        let page = offset / self.page_size;
+        let advice = match self.last_page {
+            None => self.advice,
+            Some(last_page) if page == last_page => self.advice,
+            Some(last_page) if page == last_page + 1 => {
+                self.consecutive += 1;
+                if self.consecutive >= self.scan_threshold {
+                    Advice::Sequential
+                } else {
+                    self.advice
+                }
+            }
+            Some(_) => {
+                self.consecutive = 0;
+                Advice::Random
+            }
+        };
+        self.last_page = Some(page);
+
+        if advice == self.advice {
+            return Ok(());
+        }
+        let start = (page * self.page_size).min(self.len);
+        self.madviseable
+            .madvise_range(start, self.len - start, advice)?;
+        self.advice = advice;
+        Ok(())
+    }
+}
+
+/// Prefetch the pages holding the given memory map data, see `PrefetchVirtualMemory`.
+#[cfg(windows)]
+fn prefetch(bytes: &[u8]) -> io::Result<()> {
+    use windows_sys::Win32::System::Memory::{PrefetchVirtualMemory, WIN32_MEMORY_RANGE_ENTRY};
+    use windows_sys::Win32::System::Threading::GetCurrentProcess;
+
+    if bytes.is_empty() {
+        return Ok(());
+    }
+
+    let range = WIN32_MEMORY_RANGE_ENTRY {
+        VirtualAddress: bytes.as_ptr() as *mut _,
+        NumberOfBytes: bytes.len(),
+    };
+    let result = unsafe { PrefetchVirtualMemory(GetCurrentProcess(), 1, &range, 0) };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Check a range of `len` bytes at `offset` fits within a memory map of `total` bytes.
+fn check_range(offset: usize, len: usize, total: usize) -> io::Result<()> {
+    match offset.checked_add(len) {
+        Some(end) if end <= total => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Range of {len} at offset {offset} exceeds the mmap length of {total}"),
+        )),
+    }
+}
+
+/// Guard that advises a memory map for a scoped access pattern, and restores advice when dropped
+///
+/// For example, to read a memory map sequentially while loading, and randomly afterwards. Each
+/// guard only affects its own memory map, guards for multiple maps may be nested freely.
+pub struct AdviceGuard<'a, M: Madviseable> {
+    madviseable: &'a M,
+    restore: Advice,
+}
+
+impl<'a, M: Madviseable> AdviceGuard<'a, M> {
+    /// Advise `advice` now, and `restore` when the guard is dropped.
+    pub fn new(madviseable: &'a M, advice: Advice, restore: Advice) -> io::Result<Self> {
+        madviseable.madvise(advice)?;
+        Ok(Self {
+            madviseable,
+            restore,
+        })
+    }
+
+    /// Advise [`Advice::Sequential`] now, and the global advice when the guard is dropped.
+    ///
+    /// See [`get_global`].
+    pub fn sequential(madviseable: &'a M) -> io::Result<Self> {
+        Self::new(madviseable, Advice::Sequential, get_global())
+    }
+}
+
+impl<M: Madviseable> Drop for AdviceGuard<'_, M> {
+    fn drop(&mut self) {
+        if let Err(err) = self.madviseable.madvise(self.restore) {
+            log::error!("Failed to restore {:?} advice: {err}", self.restore);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the advice given to it
+    #[derive(Default)]
+    struct Recorder(parking_lot::Mutex<Vec<&'static str>>);
+
+    impl Madviseable for Recorder {
+        fn madvise(&self, advice: Advice) -> io::Result<()> {
+            self.0.lock().push(advice.name());
+            Ok(())
+        }
+
+        fn madvise_range(&self, _offset: usize, _len: usize, advice: Advice) -> io::Result<()> {
+            self.madvise(advice)
+        }
+    }
+
+    /// Fails to advise
+    struct Failing;
+
+    impl Madviseable for Failing {
+        fn madvise(&self, _advice: Advice) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "failing"))
+        }
+
+        fn madvise_range(&self, _offset: usize, _len: usize, advice: Advice) -> io::Result<()> {
+            self.madvise(advice)
+        }
+    }
+
+    #[test]
+    fn test_advice_guard() {
+        let (first, second) = (Recorder::default(), Recorder::default());
+        {
+            let _first = AdviceGuard::new(&first, Advice::Sequential, Advice::Random).unwrap();
+            let _second = AdviceGuard::new(&second, Advice::Normal, Advice::Random).unwrap();
+            assert_eq!(*first.0.lock(), ["sequential"]);
+            assert_eq!(*second.0.lock(), ["normal"]);
+        }
+        assert_eq!(*first.0.lock(), ["sequential", "random"]);
+        assert_eq!(*second.0.lock(), ["normal", "random"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_memmap2_advice() {
+        let supported = [
+            (Advice::Normal, memmap2::Advice::Normal),
+            (Advice::Random, memmap2::Advice::Random),
+            (Advice::Sequential, memmap2::Advice::Sequential),
+            (Advice::WillNeed, memmap2::Advice::WillNeed),
+        ];
+        for (advice, expected) in supported {
+            assert_eq!(memmap2::Advice::try_from(advice).unwrap(), expected);
+            assert!(unchecked_advice(advice).is_none(), "{advice:?}");
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        for advice in [Advice::PopulateRead, Advice::PopulateWrite] {
+            let err = memmap2::Advice::try_from(advice).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported, "{advice:?}");
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let populate_read = memmap2::Advice::try_from(Advice::PopulateRead).unwrap();
+            assert_eq!(populate_read, memmap2::Advice::PopulateRead);
+            let populate_write = memmap2::Advice::try_from(Advice::PopulateWrite).unwrap();
+            assert_eq!(populate_write, memmap2::Advice::PopulateWrite);
+        }
+
+        // May discard data, only given unchecked
+        let dont_need = memmap2::Advice::try_from(Advice::DontNeed).unwrap_err();
+        assert_eq!(dont_need.kind(), io::ErrorKind::InvalidInput);
+        assert!(matches!(
+            unchecked_advice(Advice::DontNeed),
+            Some(memmap2::UncheckedAdvice::DontNeed),
+        ));
+
+        let free = memmap2::Advice::try_from(Advice::Free).unwrap_err();
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(free.kind(), io::ErrorKind::InvalidInput);
+            assert!(matches!(
+                unchecked_advice(Advice::Free),
+                Some(memmap2::UncheckedAdvice::Free),
+            ));
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            assert_eq!(free.kind(), io::ErrorKind::Unsupported);
+            assert!(unchecked_advice(Advice::Free).is_none());
+        }
+    }
+
+    #[test]
+    fn test_madvise_all() {
+        let (first, second) = (Recorder::default(), Recorder::default());
+        madvise_all(&[&first, &second], Advice::Sequential).unwrap();
+        assert_eq!(*first.0.lock(), ["sequential"]);
+        assert_eq!(*second.0.lock(), ["sequential"]);
+
+        // Advises all memory maps, even if one fails
+        let result = madvise_all(
+            &[&Failing, &first, &NoopMadvise, &Failing],
+            Advice::WillNeed,
+        );
+        assert_eq!(result.unwrap_err().to_string(), "failing");
+        assert_eq!(*first.0.lock(), ["sequential", "will_need"]);
+    }
+
+    #[test]
+    #[cfg(feature = "adaptive_advice")]
+    fn test_adaptive_advisor() {
+        let page_size = crate::common::mmap_ops::page_size();
+        let recorder = Recorder::default();
+        let mut advisor = AdaptiveAdvisor::new(&recorder, 64 * page_size);
+        advisor.scan_threshold = 4;
+
+        // Scan over a few pages
+        for offset in (0..8 * page_size).step_by(1024) {
+            advisor.access(offset).unwrap();
+        }
+        assert_eq!(advisor.advice(), Advice::Sequential);
+
+        // Jump elsewhere
+        advisor.access(32 * page_size).unwrap();
+        advisor.access(2 * page_size).unwrap();
+        assert_eq!(advisor.advice(), Advice::Random);
+        assert_eq!(*recorder.0.lock(), ["sequential", "random"]);
+    }
+
+    #[test]
+    fn test_register() {
+        // Own registry, the global one is shared with other tests running in parallel
+        let registry = AdviceRegistry::new();
+        let registered = Arc::new(Recorder::default());
+        let unregistered = Arc::new(Recorder::default());
+        let dropped = Arc::new(Recorder::default());
+        let failing = Arc::new(Failing);
+        registry.register(&registered);
+        registry.register(&unregistered);
+        registry.register(&dropped);
+        registry.register(&failing);
+        registry.unregister(&unregistered);
+        drop(dropped);
+
+        registry.advise(Advice::Sequential);
+        registry.advise(Advice::Random);
+        assert_eq!(*registered.0.lock(), ["sequential", "random"]);
+        assert!(unregistered.0.lock().is_empty());
+        assert_eq!(registry.madviseables.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_serde_advice() {
+        for (advice, json) in [
+            (Advice::Random, r#""random""#),
+            (Advice::PopulateRead, r#""populate_read""#),
+        ] {
+            assert_eq!(serde_json::to_string(&advice).unwrap(), json);
+            assert_eq!(format!("\"{advice}\""), json);
+            let parsed: Advice = serde_json::from_str(json).unwrap();
+            assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_default_advice() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(default)]
+            advice: Advice,
+        }
+
+        assert!(matches!(Advice::default(), Advice::Random));
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert!(matches!(config.advice, Advice::Random));
+    }
+
+    #[test]
+    fn test_parse_advice() {
+        assert!(matches!("normal".parse(), Ok(Advice::Normal)));
+        assert!(matches!("sequential".parse(), Ok(Advice::Sequential)));
+        assert!(matches!("populate_read".parse(), Ok(Advice::PopulateRead)));
+
+        for name in ["", "Random", "populate-read", "unknown"] {
+            let err = name.parse::<Advice>().unwrap_err();
+            assert!(err.to_string().contains("unknown variant"), "{err}");
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_madvise_applied() {
+        let mmap = memmap2::MmapMut::map_anon(crate::common::mmap_ops::page_size()).unwrap();
+        for advice in [Advice::Random, Advice::PopulateRead, Advice::PopulateWrite] {
+            let outcome = mmap.madvise_applied(advice).unwrap();
+            // Populate advice is rejected with EINVAL before kernel 5.14
+            let expected = if is_supported(advice) {
+                AdviceOutcome::Applied
+            } else {
+                AdviceOutcome::Unsupported
+            };
+            assert_eq!(outcome, expected, "{advice:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_is_supported_populate() {
+        let mmap = memmap2::MmapMut::map_anon(crate::common::mmap_ops::page_size()).unwrap();
+        for advice in [Advice::PopulateRead, Advice::PopulateWrite] {
+            // Probed once, and stays the same on later calls
+            let supported = is_supported(advice);
+            assert_eq!(is_supported(advice), supported);
+            assert_eq!(mmap.madvise(advice).is_ok(), supported, "{advice:?}");
+        }
+    }
+
+    #[test]
+    fn test_madvise_range() {
+        let page_size = crate::common::mmap_ops::page_size();
+        let mmap = memmap2::MmapMut::map_anon(4 * page_size).unwrap();
+        mmap.madvise_range(page_size, 2 * page_size, Advice::WillNeed)
+            .unwrap();
+        mmap.madvise_range(0, 4 * page_size, Advice::Sequential)
+            .unwrap();
+        mmap.madvise(Advice::Random).unwrap();
+
+        assert!(mmap
+            .madvise_range(page_size, 4 * page_size, Advice::Random)
+            .is_err());
+        assert!(mmap.madvise_range(usize::MAX, 1, Advice::Random).is_err());
+    }
+
+    #[test]
+    fn test_set_global_discarding() {
+        // Rejected before changing the global advice, so this doesn't affect other tests
+        for advice in [Advice::DontNeed, Advice::Free] {
+            let err = set_global(advice).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{advice:?}");
+            assert_ne!(get_global(), advice);
+        }
+    }
+
+    #[test]
+    fn test_madvise_discard_range() {
+        let page_size = crate::common::mmap_ops::page_size();
+        let mmap = memmap2::MmapMut::map_anon(2 * page_size).unwrap();
+
+        // Only given through the unsafe function
+        for advice in [Advice::DontNeed, Advice::Free] {
+            let err = mmap.madvise(advice).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{advice:?}");
+            let err = mmap.madvise_range(0, page_size, advice).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{advice:?}");
+        }
+
+        unsafe {
+            mmap.madvise_discard_range(0, page_size, Advice::DontNeed)
+                .unwrap();
+            mmap.madvise_discard_range(0, page_size, Advice::Random)
+                .unwrap();
+            assert!(mmap
+                .madvise_discard_range(page_size, 2 * page_size, Advice::DontNeed)
+                .is_err());
+        }
+
+        // Not implemented for other madviseables
+        let recorder = Recorder::default();
+        let err = unsafe { recorder.madvise_discard_range(0, 0, Advice::DontNeed) }.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(recorder.0.lock().is_empty());
+    }
 }
@@ -48,7 +48,7 @@ pub struct StorageConfig {
     pub hnsw_index: HnswConfig,
     #[validate]
     pub quantization: Option<QuantizationConfig>,
-    #[serde(default = "default_mmap_advice")]
+    #[serde(default)]
     pub mmap_advice: madvise::Advice,
     #[serde(default)]
     pub node_type: NodeType,
@@ -82,10 +82,6 @@ fn default_on_disk_payload() -> bool {
     false
 }
 
-fn default_mmap_advice() -> madvise::Advice {
-    madvise::Advice::Random
-}
-
 /// Information of a peer in the cluster
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 pub struct PeerInfo {
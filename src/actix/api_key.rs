@@ -1,26 +1,167 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::future::{ready, Ready};
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
 
+use ::api::grpc::models::{ApiResponse, ApiStatus};
 use actix_web::body::{BoxBody, EitherBody};
-use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
-use actix_web::{Error, HttpResponse};
-use constant_time_eq::constant_time_eq;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::PayloadError;
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::{web, Error, HttpMessage, HttpResponse};
 use futures_util::future::LocalBoxFuture;
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+use crate::common::auth::{
+    auth_trace, Access, ApiKeySources, AuthError, AuthKeys, AuthKeysHandle, AuthLogContext,
+    AuthOutcome, KeyId, RateLimitKey, UnauthenticatedPaths, AUTH_METRICS,
+};
+use crate::common::ip_allowlist::IpAllowlist;
+use crate::common::rate_limiter::RateLimiter;
+use crate::common::request_id::{RequestId, REQUEST_ID_HEADER};
+use crate::common::request_signing::{SignedRequest, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::settings::ReadOnlyRouteConfig;
 
 pub struct ApiKey {
-    api_key: String,
+    auth_keys: AuthKeysHandle,
+    key_sources: Arc<ApiKeySources>,
+    unauthenticated_paths: Arc<UnauthenticatedPaths>,
+    read_only: Arc<ReadOnlyRequests>,
+    rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
+    ip_allowlist: Option<Arc<IpAllowlist>>,
+    rejection_status: StatusCode,
+    allow_options: bool,
 }
 
 impl ApiKey {
-    pub fn new(api_key: &str) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        auth_keys: AuthKeysHandle,
+        key_sources: Arc<ApiKeySources>,
+        unauthenticated_paths: Arc<UnauthenticatedPaths>,
+        read_only: Arc<ReadOnlyRequests>,
+        rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
+        ip_allowlist: Option<Arc<IpAllowlist>>,
+        rejection_status: StatusCode,
+        allow_options: bool,
+    ) -> Self {
         Self {
-            api_key: api_key.to_string(),
+            auth_keys,
+            key_sources,
+            unauthenticated_paths,
+            read_only,
+            rate_limiter,
+            ip_allowlist,
+            rejection_status,
+            allow_options,
         }
     }
 }
 
+/// REST requests that may be made with a read-only api-key
+///
+/// By default, all GET requests are read-only. Some read operations, such as search, scroll and
+/// recommend, are POST requests however. Those must be listed as read-only routes explicitly.
+#[derive(Clone, Debug)]
+pub struct ReadOnlyRequests {
+    /// Methods permitted on any route
+    methods: HashSet<Method>,
+    routes: Vec<ReadOnlyRoute>,
+}
+
+impl ReadOnlyRequests {
+    pub fn from_config(methods: &[String], routes: &[ReadOnlyRouteConfig]) -> io::Result<Self> {
+        Ok(Self {
+            methods: methods
+                .iter()
+                .map(|method| parse_method(method))
+                .collect::<io::Result<_>>()?,
+            routes: ReadOnlyRoute::from_config(routes)?,
+        })
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        self.methods.contains(method) || self.routes.iter().any(|route| route.matches(method, path))
+    }
+}
+
+/// REST route that may be called with a read-only api-key, regardless of the read-only methods
+#[derive(Clone, Debug)]
+pub struct ReadOnlyRoute {
+    method: Method,
+    segments: Vec<String>,
+}
+
+impl ReadOnlyRoute {
+    /// Create route from HTTP method and path pattern.
+    ///
+    /// The pattern is matched as a prefix, segment by segment. A `{placeholder}` segment matches
+    /// any single path segment. For example, `/collections/{name}/points/search` matches
+    /// `/collections/test/points/search` and `/collections/test/points/search/batch`.
+    pub fn new(method: Method, pattern: &str) -> Self {
+        Self {
+            method,
+            segments: path_segments(pattern).map(str::to_string).collect(),
+        }
+    }
+
+    pub fn from_config(routes: &[ReadOnlyRouteConfig]) -> io::Result<Vec<Self>> {
+        routes
+            .iter()
+            .map(|route| Ok(Self::new(parse_method(&route.method)?, &route.path)))
+            .collect()
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        if *method != self.method {
+            return false;
+        }
+
+        let mut path_segments = path_segments(path);
+        self.segments
+            .iter()
+            .all(|segment| match path_segments.next() {
+                Some(path_segment) => is_placeholder(segment) || segment == path_segment,
+                None => false,
+            })
+    }
+}
+
+fn parse_method(method: &str) -> io::Result<Method> {
+    Method::from_bytes(method.to_uppercase().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn is_placeholder(segment: &str) -> bool {
+    segment.starts_with('{') && segment.ends_with('}')
+}
+
+/// Whether a modifying request manages collections, aliases, snapshots or the cluster, rather
+/// than points.
+fn is_admin_path(path: &str) -> bool {
+    let segments: Vec<_> = path_segments(path).collect();
+    matches!(
+        segments.as_slice(),
+        ["collections", _]
+            | ["collections", _, "snapshots", ..]
+            | ["collections", _, "cluster", ..]
+            | ["snapshots", ..]
+            | ["cluster", ..]
+            | ["locks", ..]
+    )
+}
+
 impl<S, B> Transform<S, ServiceRequest> for ApiKey
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<EitherBody<B, BoxBody>>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<EitherBody<B, BoxBody>>, Error = Error>
+        + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -32,20 +173,144 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(ApiKeyMiddleware {
-            api_key: self.api_key.clone(),
-            service,
+            auth_keys: self.auth_keys.clone(),
+            key_sources: self.key_sources.clone(),
+            unauthenticated_paths: self.unauthenticated_paths.clone(),
+            read_only: self.read_only.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            ip_allowlist: self.ip_allowlist.clone(),
+            rejection_status: self.rejection_status,
+            allow_options: self.allow_options,
+            service: Rc::new(service),
         }))
     }
 }
 
 pub struct ApiKeyMiddleware<S> {
-    api_key: String,
-    service: S,
+    auth_keys: AuthKeysHandle,
+    key_sources: Arc<ApiKeySources>,
+    unauthenticated_paths: Arc<UnauthenticatedPaths>,
+    read_only: Arc<ReadOnlyRequests>,
+    rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
+    ip_allowlist: Option<Arc<IpAllowlist>>,
+    rejection_status: StatusCode,
+    /// Let OPTIONS requests through without an api-key, so CORS preflight requests can be answered
+    allow_options: bool,
+    service: Rc<S>,
+}
+
+impl<S> ApiKeyMiddleware<S> {
+    /// Access required by the request. Read-only api-keys may be used for the configured
+    /// read-only methods on any route, and for the configured read-only routes.
+    fn request_access(&self, req: &ServiceRequest) -> Access {
+        if self.read_only.matches(req.method(), req.path()) {
+            Access::Read
+        } else if is_admin_path(req.path()) {
+            Access::Admin
+        } else {
+            Access::Write
+        }
+    }
+
+    /// Check the api-key and rate limit of the request.
+    ///
+    /// `key` is the api-key of the request, see [`ApiKeyMiddleware::request_key`].
+    fn authenticate(
+        &self,
+        req: &ServiceRequest,
+        key: Option<Result<&str, AuthError>>,
+        access: Access,
+        auth_keys: &AuthKeys,
+    ) -> AuthOutcome {
+        // JWT bearer tokens are tried first, anything else is checked as api-key
+        let authorization = req.headers().get(header::AUTHORIZATION);
+        if let Some(token) = auth_keys.jwt_bearer_token(authorization) {
+            auth_trace!("{} {}: checking jwt bearer token", req.method(), req.path());
+            return match auth_keys.verify_jwt(token, access) {
+                Ok((key_id, rate_limit_key)) => {
+                    AuthOutcome::authenticated(key_id, rate_limit_key, self.rate_limiter.as_deref())
+                }
+                Err(err) => AuthOutcome::from(err),
+            };
+        }
+
+        auth_trace!("{} {}: checking api-key", req.method(), req.path());
+        // `None` if the request has no credentials
+        let authenticated = key.map(|key| {
+            key.and_then(|key| {
+                let key_id = auth_keys.authenticate(key, access)?;
+                Ok((key_id, RateLimitKey::digest(key)))
+            })
+        });
+
+        match authenticated {
+            // Missing credentials
+            None => {
+                auth_keys.compare_missing_key();
+                AuthOutcome::Unauthorized
+            }
+            // Wrong credentials
+            Some(Err(err)) => AuthOutcome::from(err),
+            Some(Ok((key_id, rate_limit_key))) => {
+                AuthOutcome::authenticated(key_id, rate_limit_key, self.rate_limiter.as_deref())
+            }
+        }
+    }
+
+    /// Check the client address against the ip allowlist, if configured.
+    fn address_allowed(&self, req: &ServiceRequest) -> bool {
+        match &self.ip_allowlist {
+            Some(ip_allowlist) => {
+                let forwarded_for = req
+                    .headers()
+                    .get_all(header::X_FORWARDED_FOR)
+                    .last()
+                    .and_then(|value| value.to_str().ok());
+                ip_allowlist.allows(req.peer_addr().map(|addr| addr.ip()), forwarded_for)
+            }
+            None => true,
+        }
+    }
+
+    /// Api-key from the configured header.
+    ///
+    /// If enabled, a bearer token or Basic auth password in the `Authorization` header takes
+    /// precedence, and the `api_key` query parameter is used as fallback when no header is present.
+    fn request_key<'a>(&self, req: &'a ServiceRequest) -> Option<Result<Cow<'a, str>, AuthError>> {
+        let authorization = req.headers().get(header::AUTHORIZATION);
+        if let Some(key) = self.key_sources.authorization_key(authorization) {
+            return Some(key);
+        }
+
+        match req.headers().get(self.key_sources.header_name.as_str()) {
+            Some(key) => Some(self.key_sources.header_key(key).map(Cow::Borrowed)),
+            None if self.key_sources.query => query_api_key(req.query_string()).map(|key| {
+                auth_trace!("api-key from query parameter");
+                self.key_sources.check_length(key.len())?;
+                Ok(Cow::Owned(key))
+            }),
+            None => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiKeyQuery {
+    api_key: Option<String>,
+}
+
+/// Api-key from the `api_key` query parameter. Malformed queries are treated as having no key.
+fn query_api_key(query: &str) -> Option<String> {
+    web::Query::<ApiKeyQuery>::from_query(query)
+        .ok()?
+        .into_inner()
+        .api_key
 }
 
 impl<S, B> Service<ServiceRequest> for ApiKeyMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<EitherBody<B, BoxBody>>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<EitherBody<B, BoxBody>>, Error = Error>
+        + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -56,18 +321,636 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        if let Some(key) = req.headers().get("api-key") {
-            if let Ok(key) = key.to_str() {
-                if constant_time_eq(self.api_key.as_bytes(), key.as_bytes()) {
-                    return Box::pin(self.service.call(req));
+        let request_id = RequestId::from_header(
+            req.headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        );
+        req.extensions_mut().insert(request_id.clone());
+
+        let future = self.authenticate_and_call(req, request_id.clone());
+        Box::pin(async move {
+            let mut response = future.await?;
+            if let Ok(value) = header::HeaderValue::from_str(request_id.as_str()) {
+                response
+                    .headers_mut()
+                    .insert(header::HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+impl<S, B> ApiKeyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<EitherBody<B, BoxBody>>, Error = Error>
+        + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    /// Check the api-key of the request, and call the wrapped service if it is permitted.
+    fn authenticate_and_call(
+        &self,
+        req: ServiceRequest,
+        request_id: RequestId,
+    ) -> LocalBoxFuture<'static, Result<ServiceResponse<EitherBody<B, BoxBody>>, Error>> {
+        let is_allowed_options = self.allow_options && req.method() == Method::OPTIONS;
+        if is_allowed_options || self.unauthenticated_paths.contains(req.path()) {
+            auth_trace!("{} {}: skipped, unauthenticated", req.method(), req.path());
+            return Box::pin(self.service.call(req));
+        }
+
+        let access = self.request_access(&req);
+        let auth_keys = self.auth_keys.load_full();
+        let is_signed =
+            req.headers().contains_key(SIGNATURE_HEADER) && auth_keys.request_signer().is_some();
+        // Parsed once, for both checking and logging the api-key
+        let request_key = self.request_key(&req);
+        let key = request_key
+            .as_ref()
+            .map(|key| key.as_deref().map_err(|&err| err));
+
+        // Checked before the api-key, so disallowed addresses don't get to probe for keys
+        let outcome = if access != Access::Read && !self.address_allowed(&req) {
+            auth_trace!("{} {}: address not allowed", req.method(), req.path());
+            AuthOutcome::AddressNotAllowed
+        } else if is_signed {
+            auth_trace!("{} {}: checking signature", req.method(), req.path());
+            let service = self.service.clone();
+            let rate_limiter = self.rate_limiter.clone();
+            let rejection_status = self.rejection_status;
+            return Box::pin(async move {
+                let mut req = req;
+                let outcome = match authenticate_signed(&mut req, &auth_keys).await? {
+                    Ok((key_id, rate_limit_key)) => {
+                        AuthOutcome::authenticated(key_id, rate_limit_key, rate_limiter.as_deref())
+                    }
+                    Err(err) => AuthOutcome::from(err),
+                };
+                AUTH_METRICS.record(outcome);
+                log_outcome(&req, None, &request_id, outcome, &auth_keys);
+
+                match rejection_response(outcome, rejection_status) {
+                    Some(response) => Ok(req.into_response(response).map_into_right_body()),
+                    None => {
+                        insert_auth_context(&req, outcome, &auth_keys);
+                        service.call(req).await
+                    }
                 }
+            });
+        } else {
+            self.authenticate(&req, key, access, &auth_keys)
+        };
+        AUTH_METRICS.record(outcome);
+        log_outcome(
+            &req,
+            key.and_then(Result::ok),
+            &request_id,
+            outcome,
+            &auth_keys,
+        );
+
+        match rejection_response(outcome, self.rejection_status) {
+            Some(response) => {
+                Box::pin(async { Ok(req.into_response(response).map_into_right_body()) })
+            }
+            None => {
+                insert_auth_context(&req, outcome, &auth_keys);
+                Box::pin(self.service.call(req))
             }
         }
+    }
+}
 
-        Box::pin(async {
-            Ok(req
-                .into_response(HttpResponse::Forbidden().body("Invalid api-key"))
-                .map_into_right_body())
-        })
+/// Verify the signature of a signed request.
+///
+/// The body is covered by the signature, so it is buffered and put back for the wrapped service.
+async fn authenticate_signed(
+    req: &mut ServiceRequest,
+    auth_keys: &AuthKeys,
+) -> Result<Result<(KeyId, RateLimitKey), AuthError>, Error> {
+    let max_body_size = match auth_keys.request_signer() {
+        Some(request_signer) => request_signer.max_body_size(),
+        None => return Ok(Err(AuthError::Invalid)),
+    };
+
+    let mut payload = req.take_payload();
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > max_body_size {
+            return Err(PayloadError::Overflow.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = body.freeze();
+    req.set_payload(Payload::from(body.clone()));
+
+    let path = match req.uri().path_and_query() {
+        Some(path_and_query) => path_and_query.as_str(),
+        None => req.path(),
+    };
+    let signed_request = SignedRequest {
+        method: req.method().as_str(),
+        path,
+        timestamp: header_str(req, TIMESTAMP_HEADER),
+        signature: header_str(req, SIGNATURE_HEADER),
+        body: &body,
+    };
+
+    Ok(auth_keys.verify_signature(&signed_request))
+}
+
+/// Value of the given header, or an empty string if missing or not valid UTF-8
+fn header_str<'a>(req: &'a ServiceRequest, name: &str) -> &'a str {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+}
+
+/// Log the outcome of the api-key check of the request, with the api-key it was sent with.
+fn log_outcome(
+    req: &ServiceRequest,
+    key: Option<&str>,
+    request_id: &RequestId,
+    outcome: AuthOutcome,
+    auth_keys: &AuthKeys,
+) {
+    let context = AuthLogContext {
+        method: req.method().as_str(),
+        path: req.path(),
+        peer: req.peer_addr().map(|addr| addr.ip()),
+        key,
+        request_id,
+    };
+    context.log(outcome, auth_keys);
+}
+
+/// Make the key the request was authenticated with available to handlers, as `AuthContext`.
+fn insert_auth_context(req: &ServiceRequest, outcome: AuthOutcome, auth_keys: &AuthKeys) {
+    if let AuthOutcome::Ok(key_id) = outcome {
+        req.extensions_mut().insert(auth_keys.auth_context(key_id));
+    }
+}
+
+/// Response rejecting the request, or `None` if the request may pass
+///
+/// Requests that are not permitted are rejected with the configured `rejection_status`.
+fn rejection_response(outcome: AuthOutcome, rejection_status: StatusCode) -> Option<HttpResponse> {
+    let response = match outcome {
+        AuthOutcome::Ok(_) => return None,
+        AuthOutcome::Unauthorized => unauthorized_response(),
+        // Expired keys get the same response as invalid ones, only logs and metrics differ
+        AuthOutcome::Forbidden | AuthOutcome::Expired => {
+            forbidden_response(rejection_status, "Invalid api-key")
+        }
+        AuthOutcome::RateLimited => too_many_requests_response(),
+        AuthOutcome::AddressNotAllowed => {
+            forbidden_response(rejection_status, "Address not allowed to modify data")
+        }
+        AuthOutcome::InvalidToken => invalid_token_response(),
+    };
+    Some(response)
+}
+
+/// Response for requests without an api-key
+fn unauthorized_response() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, "ApiKey"))
+        .json(error_body("Missing api-key"))
+}
+
+/// Response for requests with an invalid or expired JWT bearer token
+fn invalid_token_response() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#))
+        .json(error_body("Invalid token"))
+}
+
+/// Response for requests that are not permitted, 403 Forbidden unless configured otherwise
+fn forbidden_response(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(error_body(message))
+}
+
+/// Response for requests exceeding the rate limit of their api-key
+fn too_many_requests_response() -> HttpResponse {
+    HttpResponse::TooManyRequests().json(error_body("Too many requests for api-key"))
+}
+
+/// Error body in the same shape as other API errors
+fn error_body(message: &str) -> ApiResponse<()> {
+    ApiResponse {
+        result: None,
+        status: ApiStatus::Error(message.to_string()),
+        time: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{self, TestRequest};
+    use actix_web::App;
+    use futures_util::TryFutureExt;
+
+    use super::*;
+    use crate::common::auth::{ApiKeys, AuthContext};
+    use crate::settings::{
+        default_read_only_methods, default_read_only_routes, UnauthenticatedPathConfig,
+    };
+
+    /// Request sent with the given api-key, if any, and the status it is expected to get
+    type Case<'a> = (Method, &'a str, Option<&'a str>, StatusCode);
+
+    fn api_key_middleware(auth_keys: AuthKeys, rejection_status: StatusCode) -> ApiKey {
+        api_key_middleware_with_options(auth_keys, rejection_status, true)
+    }
+
+    fn api_key_middleware_with_options(
+        auth_keys: AuthKeys,
+        rejection_status: StatusCode,
+        allow_options: bool,
+    ) -> ApiKey {
+        let key_sources = ApiKeySources {
+            header_name: "api-key".into(),
+            bearer: false,
+            basic: false,
+            query: false,
+            max_length: 1024,
+        };
+        let unauthenticated_paths = UnauthenticatedPaths::new(vec![UnauthenticatedPathConfig {
+            path: "/healthz".into(),
+            prefix: false,
+        }]);
+        ApiKey::new(
+            AuthKeysHandle::new(auth_keys),
+            Arc::new(key_sources),
+            Arc::new(unauthenticated_paths),
+            Arc::new(
+                ReadOnlyRequests::from_config(
+                    &default_read_only_methods(),
+                    &default_read_only_routes(),
+                )
+                .unwrap(),
+            ),
+            None,
+            None,
+            rejection_status,
+            allow_options,
+        )
+    }
+
+    /// Drive each request through the middleware, wrapping a service that responds with 200 OK to
+    /// anything, and check the status of the response.
+    async fn assert_statuses(middleware: ApiKey, cases: &[Case<'_>]) {
+        let app = test::init_service(
+            App::new()
+                .wrap_fn(|req, srv| srv.call(req).map_ok(ServiceResponse::map_into_left_body))
+                .wrap(middleware)
+                .default_service(web::to(|| async { HttpResponse::Ok() })),
+        )
+        .await;
+
+        for (method, path, key, expected) in cases {
+            let mut request = TestRequest::default().method(method.clone()).uri(path);
+            if let Some(key) = key {
+                request = request.insert_header(("api-key", *key));
+            }
+            let status = test::call_service(&app, request.to_request())
+                .await
+                .status();
+            assert_eq!(status, *expected, "{method} {path} with api-key {key:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_statuses() {
+        let auth_keys = AuthKeys::new(
+            ApiKeys::new(vec!["read-write".into()]),
+            ApiKeys::new(vec!["read-only".into()]),
+        );
+        let rw = Some("read-write");
+        let ro = Some("read-only");
+        let other = Some("other");
+
+        assert_statuses(
+            api_key_middleware(auth_keys, StatusCode::FORBIDDEN),
+            &[
+                // Read-write key may do everything
+                (Method::GET, "/collections", rw, StatusCode::OK),
+                (Method::PUT, "/collections/a/points", rw, StatusCode::OK),
+                (Method::DELETE, "/collections/a", rw, StatusCode::OK),
+                // Read-only key may only read, including read operations that use POST
+                (Method::GET, "/collections", ro, StatusCode::OK),
+                (
+                    Method::POST,
+                    "/collections/a/points/search",
+                    ro,
+                    StatusCode::OK,
+                ),
+                (
+                    Method::POST,
+                    "/collections/a/points/delete",
+                    ro,
+                    StatusCode::FORBIDDEN,
+                ),
+                (
+                    Method::PUT,
+                    "/collections/a/points",
+                    ro,
+                    StatusCode::FORBIDDEN,
+                ),
+                (Method::DELETE, "/collections/a", ro, StatusCode::FORBIDDEN),
+                // Wrong or missing key
+                (Method::GET, "/collections", other, StatusCode::FORBIDDEN),
+                (
+                    Method::POST,
+                    "/collections/a/points",
+                    other,
+                    StatusCode::FORBIDDEN,
+                ),
+                (Method::GET, "/collections", None, StatusCode::UNAUTHORIZED),
+                (
+                    Method::POST,
+                    "/collections/a/points",
+                    None,
+                    StatusCode::UNAUTHORIZED,
+                ),
+                // Unauthenticated paths need no key
+                (Method::GET, "/healthz", None, StatusCode::OK),
+                (Method::GET, "/healthz", other, StatusCode::OK),
+            ],
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_auth_context() {
+        let auth_keys = AuthKeys::new(
+            ApiKeys::new(vec!["read-write".into()]),
+            ApiKeys::new(vec!["read-only".into()]),
+        );
+        let app = test::init_service(
+            App::new()
+                .wrap_fn(|req, srv| srv.call(req).map_ok(ServiceResponse::map_into_left_body))
+                .wrap(api_key_middleware(auth_keys, StatusCode::FORBIDDEN))
+                .default_service(web::to(|context: web::ReqData<AuthContext>| async move {
+                    format!("{} {}", context.key_label, context.is_read_only)
+                })),
+        )
+        .await;
+
+        for (key, expected) in [
+            ("read-write", "read-write #0 false"),
+            ("read-only", "read-only #0 true"),
+        ] {
+            let request = TestRequest::get()
+                .uri("/collections")
+                .insert_header(("api-key", key))
+                .to_request();
+            let body = test::call_and_read_body(&app, request).await;
+            assert_eq!(body, expected.as_bytes());
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_request_id() {
+        let auth_keys = AuthKeys::new(ApiKeys::new(vec!["key".into()]), ApiKeys::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_fn(|req, srv| srv.call(req).map_ok(ServiceResponse::map_into_left_body))
+                .wrap(api_key_middleware(auth_keys, StatusCode::FORBIDDEN))
+                .default_service(web::to(|request_id: web::ReqData<RequestId>| async move {
+                    request_id.to_string()
+                })),
+        )
+        .await;
+
+        // Id of the client is propagated to handlers and the response
+        let request = TestRequest::get()
+            .uri("/collections")
+            .insert_header(("api-key", "key"))
+            .insert_header((REQUEST_ID_HEADER, "proxy-1234"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "proxy-1234"
+        );
+        assert_eq!(test::read_body(response).await, "proxy-1234".as_bytes());
+
+        // Otherwise an id is generated, also for rejected requests
+        let request = TestRequest::get().uri("/collections").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let request_id = response.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert!(uuid::Uuid::parse_str(request_id.to_str().unwrap()).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_options() {
+        let auth_keys = || AuthKeys::new(ApiKeys::new(vec!["key".into()]), ApiKeys::default());
+
+        assert_statuses(
+            api_key_middleware_with_options(auth_keys(), StatusCode::FORBIDDEN, true),
+            &[
+                (Method::OPTIONS, "/collections", None, StatusCode::OK),
+                (Method::GET, "/collections", None, StatusCode::UNAUTHORIZED),
+                (
+                    Method::POST,
+                    "/collections/a/points",
+                    None,
+                    StatusCode::UNAUTHORIZED,
+                ),
+            ],
+        )
+        .await;
+        assert_statuses(
+            api_key_middleware_with_options(auth_keys(), StatusCode::FORBIDDEN, false),
+            &[(
+                Method::OPTIONS,
+                "/collections",
+                None,
+                StatusCode::UNAUTHORIZED,
+            )],
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_rejection_status() {
+        let auth_keys = AuthKeys::new(ApiKeys::new(vec!["key".into()]), ApiKeys::default());
+
+        assert_statuses(
+            api_key_middleware(auth_keys, StatusCode::NOT_FOUND),
+            &[
+                (Method::GET, "/collections", Some("key"), StatusCode::OK),
+                (
+                    Method::GET,
+                    "/collections",
+                    Some("other"),
+                    StatusCode::NOT_FOUND,
+                ),
+            ],
+        )
+        .await;
+    }
+
+    #[test]
+    fn test_invalid_token_response_status() {
+        // Also used for tokens lacking the required access, regardless of the rejection status
+        for rejection_status in [StatusCode::FORBIDDEN, StatusCode::NOT_FOUND] {
+            let outcome = AuthOutcome::from(AuthError::InvalidToken);
+            let response = rejection_response(outcome, rejection_status).unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let authenticate = response.headers().get(header::WWW_AUTHENTICATE).unwrap();
+            assert_eq!(
+                authenticate.to_str().unwrap(),
+                r#"Bearer error="invalid_token""#,
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_without_keys() {
+        // As used when authentication is required, but no key is configured
+        assert_statuses(
+            api_key_middleware(AuthKeys::default(), StatusCode::FORBIDDEN),
+            &[
+                (Method::GET, "/collections", None, StatusCode::UNAUTHORIZED),
+                (Method::GET, "/collections", Some(""), StatusCode::FORBIDDEN),
+                (
+                    Method::GET,
+                    "/collections",
+                    Some("key"),
+                    StatusCode::FORBIDDEN,
+                ),
+                (Method::GET, "/healthz", None, StatusCode::OK),
+            ],
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn test_middleware_oversized_key() {
+        let longest = "a".repeat(1024);
+        let oversized = "b".repeat(1025);
+        let auth_keys = AuthKeys::new(
+            ApiKeys::new(vec![longest.clone(), oversized.clone()]),
+            ApiKeys::default(),
+        );
+
+        // Rejected by its length alone, even though it is a configured key
+        assert_statuses(
+            api_key_middleware(auth_keys, StatusCode::FORBIDDEN),
+            &[
+                (
+                    Method::GET,
+                    "/collections",
+                    Some(longest.as_str()),
+                    StatusCode::OK,
+                ),
+                (
+                    Method::GET,
+                    "/collections",
+                    Some(oversized.as_str()),
+                    StatusCode::FORBIDDEN,
+                ),
+            ],
+        )
+        .await;
+    }
+
+    #[test]
+    fn test_read_only_route_matches() {
+        let route = ReadOnlyRoute::new(Method::POST, "/collections/{name}/points/search");
+
+        assert!(route.matches(&Method::POST, "/collections/test/points/search"));
+        assert!(route.matches(&Method::POST, "/collections/test/points/search/"));
+        assert!(route.matches(&Method::POST, "/collections/test/points/search/batch"));
+        assert!(!route.matches(&Method::PUT, "/collections/test/points/search"));
+        assert!(!route.matches(&Method::POST, "/collections/test/points"));
+        assert!(!route.matches(&Method::POST, "/collections/test/points/delete"));
+        assert!(!route.matches(&Method::POST, "/collections/test/points/searches"));
+        assert!(!route.matches(&Method::POST, "/aliases/test/points/search"));
+    }
+
+    #[test]
+    fn test_default_read_only_routes() {
+        let routes =
+            ReadOnlyRoute::from_config(&crate::settings::default_read_only_routes()).unwrap();
+        let is_read_only =
+            |method: Method, path: &str| routes.iter().any(|route| route.matches(&method, path));
+
+        assert!(is_read_only(Method::POST, "/collections/a/points/search"));
+        assert!(is_read_only(
+            Method::POST,
+            "/collections/a/points/search/batch"
+        ));
+        assert!(is_read_only(Method::POST, "/collections/a/points/scroll"));
+        assert!(is_read_only(
+            Method::POST,
+            "/collections/a/points/recommend"
+        ));
+        assert!(!is_read_only(Method::POST, "/collections/a/points/delete"));
+        assert!(!is_read_only(Method::PUT, "/collections/a/points"));
+    }
+
+    #[test]
+    fn test_admin_paths() {
+        assert!(is_admin_path("/collections/test"));
+        assert!(is_admin_path("/collections/aliases"));
+        assert!(is_admin_path("/collections/test/snapshots/upload"));
+        assert!(is_admin_path("/collections/test/cluster"));
+        assert!(is_admin_path("/snapshots"));
+        assert!(is_admin_path("/cluster/peer/1"));
+        assert!(is_admin_path("/locks"));
+        assert!(!is_admin_path("/collections/test/points"));
+        assert!(!is_admin_path("/collections/test/points/delete"));
+        assert!(!is_admin_path("/collections/test/index"));
+    }
+
+    #[test]
+    fn test_query_api_key() {
+        assert_eq!(query_api_key("api_key=secret").as_deref(), Some("secret"));
+        assert_eq!(
+            query_api_key("wait=true&api_key=se%20cret").as_deref(),
+            Some("se cret"),
+        );
+        assert_eq!(query_api_key(""), None);
+        assert_eq!(query_api_key("wait=true"), None);
+        assert_eq!(query_api_key("api_key=a&api_key=b"), None);
+        assert_eq!(query_api_key("api_key=%ZZ&&=="), None);
+    }
+
+    #[test]
+    fn test_read_only_methods() {
+        let read_only = ReadOnlyRequests::from_config(
+            &["get".into(), "HEAD".into()],
+            &default_read_only_routes(),
+        )
+        .unwrap();
+
+        assert!(read_only.matches(&Method::GET, "/collections"));
+        assert!(read_only.matches(&Method::HEAD, "/collections"));
+        assert!(read_only.matches(&Method::POST, "/collections/a/points/search"));
+        assert!(!read_only.matches(&Method::POST, "/collections/a/points"));
+        assert!(!read_only.matches(&Method::DELETE, "/collections/a"));
+
+        // Without any read-only methods, only the read-only routes remain
+        let read_only = ReadOnlyRequests::from_config(&[], &default_read_only_routes()).unwrap();
+        assert!(!read_only.matches(&Method::GET, "/collections"));
+        assert!(read_only.matches(&Method::POST, "/collections/a/points/scroll"));
+
+        assert!(ReadOnlyRequests::from_config(&["not a method".into()], &[]).is_err());
+    }
+
+    #[test]
+    fn test_read_only_route_invalid_method() {
+        let routes = [ReadOnlyRouteConfig {
+            method: "not a method".into(),
+            path: "/collections".into(),
+        }];
+        assert!(ReadOnlyRoute::from_config(&routes).is_err());
     }
 }
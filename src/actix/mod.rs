@@ -12,6 +12,7 @@ use ::api::grpc::models::{ApiResponse, ApiStatus, VersionInfo};
 use actix_cors::Cors;
 use actix_multipart::form::tempfile::TempFileConfig;
 use actix_multipart::form::MultipartFormConfig;
+use actix_web::http::StatusCode;
 use actix_web::middleware::{Compress, Condition, Logger};
 use actix_web::{error, get, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use collection::operations::validation;
@@ -27,7 +28,10 @@ use crate::actix::api::search_api::config_search_api;
 use crate::actix::api::service_api::config_service_api;
 use crate::actix::api::snapshot_api::config_snapshots_api;
 use crate::actix::api::update_api::config_update_api;
-use crate::actix::api_key::ApiKey;
+use crate::actix::api_key::{ApiKey, ReadOnlyRequests};
+use crate::common::auth::{ApiKeySources, AuthKeysHandle, RateLimitKey, UnauthenticatedPaths};
+use crate::common::ip_allowlist::IpAllowlist;
+use crate::common::rate_limiter::RateLimiter;
 use crate::common::telemetry::TelemetryCollector;
 use crate::settings::{max_web_workers, Settings};
 
@@ -41,6 +45,8 @@ pub fn init(
     dispatcher: Arc<Dispatcher>,
     telemetry_collector: Arc<tokio::sync::Mutex<TelemetryCollector>>,
     settings: Settings,
+    auth_keys: Option<AuthKeysHandle>,
+    rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
 ) -> std::io::Result<()> {
     actix_web::rt::System::new().block_on(async {
         let toc_data = web::Data::from(dispatcher.toc().clone());
@@ -51,7 +57,23 @@ pub fn init(
             .actix_telemetry_collector
             .clone();
         let telemetry_collector_data = web::Data::from(telemetry_collector);
-        let api_key = settings.service.api_key.clone();
+        let key_sources = Arc::new(ApiKeySources::from_config(&settings.service)?);
+        let unauthenticated_paths = Arc::new(UnauthenticatedPaths::new(
+            settings.service.unauthenticated_paths.clone(),
+        ));
+        let ip_allowlist = settings
+            .service
+            .write_ip_allowlist
+            .as_ref()
+            .map(IpAllowlist::from_config)
+            .transpose()?
+            .map(Arc::new);
+        let read_only = Arc::new(ReadOnlyRequests::from_config(
+            &settings.service.read_only_methods,
+            &settings.service.read_only_routes,
+        )?);
+        let rejection_status = StatusCode::from_u16(settings.service.api_key_rejection_status)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
         let mut server = HttpServer::new(move || {
             let cors = Cors::default()
                 .allow_any_origin()
@@ -70,8 +92,17 @@ pub fn init(
                 // api_key middleware
                 // note: the last call to `wrap()` or `wrap_fn()` is executed first
                 .wrap(Condition::new(
-                    api_key.is_some(),
-                    ApiKey::new(&api_key.clone().unwrap_or_default()),
+                    auth_keys.is_some(),
+                    ApiKey::new(
+                        auth_keys.clone().unwrap_or_default(),
+                        key_sources.clone(),
+                        unauthenticated_paths.clone(),
+                        read_only.clone(),
+                        rate_limiter.clone(),
+                        ip_allowlist.clone(),
+                        rejection_status,
+                        settings.service.allow_unauthenticated_options,
+                    ),
                 ))
                 .wrap(Condition::new(settings.service.enable_cors, cors))
                 .wrap(Logger::default().exclude("/")) // Avoid logging healthcheck requests
@@ -0,0 +1,1544 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use arc_swap::{ArcSwap, Guard};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use constant_time_eq::constant_time_eq;
+use ring::digest;
+use serde::Deserialize;
+use tonic::codegen::http::header::{HeaderName, HeaderValue};
+
+use crate::common::jwt::{self, JwtVerifier};
+use crate::common::rate_limiter::RateLimiter;
+use crate::common::request_id::RequestId;
+use crate::common::request_signing::{RequestSigner, SignedRequest};
+use crate::settings::{
+    ApiKeyConfig, ScopedApiKeyConfig, ServiceConfig, Settings, UnauthenticatedPathConfig,
+};
+
+/// Log at trace level which branch of the api-key check a request takes, if the `auth-debug`
+/// feature is enabled. Expands to nothing otherwise.
+///
+/// Must never be given the api-key itself, only which kind of key or credentials matched.
+#[cfg(feature = "auth-debug")]
+macro_rules! auth_trace {
+    ($($arg:tt)+) => {
+        log::trace!(target: "auth_debug", $($arg)+)
+    };
+}
+
+#[cfg(not(feature = "auth-debug"))]
+macro_rules! auth_trace {
+    ($($arg:tt)+) => {};
+}
+
+pub(crate) use auth_trace;
+
+/// Where requests may carry their api-key
+#[derive(Clone, Debug)]
+pub struct ApiKeySources {
+    /// Name of the header carrying the api-key
+    pub header_name: String,
+    /// Accept the api-key as bearer token in the `Authorization` header, which takes precedence
+    pub bearer: bool,
+    /// Accept the api-key as password of Basic auth in the `Authorization` header, which takes
+    /// precedence as well
+    pub basic: bool,
+    /// Accept the api-key from the `api_key` query parameter if no header is present (REST only)
+    pub query: bool,
+    /// Longest api-key accepted, in bytes
+    pub max_length: usize,
+}
+
+impl ApiKeySources {
+    pub fn from_config(service_config: &ServiceConfig) -> io::Result<Self> {
+        let header_name = service_config.api_key_header.clone();
+        HeaderName::from_bytes(header_name.as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        Ok(Self {
+            header_name,
+            bearer: service_config.api_key_bearer,
+            basic: service_config.api_key_basic_auth,
+            query: service_config.api_key_in_query,
+            max_length: service_config.api_key_max_length,
+        })
+    }
+
+    /// Api-key carried by the given header value.
+    ///
+    /// Values longer than `max_length` are rejected before decoding them, so oversized headers
+    /// can't waste time on decoding and comparing them against every configured key.
+    pub fn header_key<'a>(&self, value: &'a HeaderValue) -> Result<&'a str, AuthError> {
+        self.check_length(value.len())?;
+        value.to_str().map_err(|_| AuthError::Invalid)
+    }
+
+    /// Api-key carried by the `Authorization` header, as bearer token or as password of Basic
+    /// auth, if enabled.
+    ///
+    /// Returns `None` if the header carries no api-key in any of the enabled schemes.
+    pub fn authorization_key<'a>(
+        &self,
+        authorization: Option<&'a HeaderValue>,
+    ) -> Option<Result<Cow<'a, str>, AuthError>> {
+        if !self.bearer && !self.basic {
+            return None;
+        }
+        let authorization = match self.header_key(authorization?) {
+            Ok(authorization) => authorization,
+            Err(err) => return Some(Err(err)),
+        };
+
+        if self.bearer {
+            if let Some(token) = bearer_token(authorization) {
+                auth_trace!("api-key from bearer token");
+                return Some(Ok(Cow::Borrowed(token)));
+            }
+        }
+        if self.basic {
+            if let Some(password) = basic_auth_password(authorization) {
+                auth_trace!("api-key from Basic auth password");
+                return Some(password.map(Cow::Owned));
+            }
+        }
+        auth_trace!("no api-key in authorization header");
+        None
+    }
+
+    /// Reject api-keys longer than `max_length`.
+    pub fn check_length(&self, length: usize) -> Result<(), AuthError> {
+        if length > self.max_length {
+            log::debug!("Rejected request with api-key of {length} bytes");
+            return Err(AuthError::Invalid);
+        }
+        Ok(())
+    }
+}
+
+/// Token of an `Authorization: Bearer <token>` header value. The scheme is case-insensitive.
+pub fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") {
+        Some(token.trim_start())
+    } else {
+        None
+    }
+}
+
+/// Password of an `Authorization: Basic <credentials>` header value. The username is ignored.
+///
+/// Returns `None` for other schemes, and an error if the credentials are malformed.
+fn basic_auth_password(authorization: &str) -> Option<Result<String, AuthError>> {
+    let (scheme, credentials) = authorization.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+
+    let password = STANDARD
+        .decode(credentials.trim())
+        .ok()
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+        .and_then(|credentials| {
+            let (_username, password) = credentials.split_once(':')?;
+            Some(password.to_string())
+        });
+    if password.is_none() {
+        log::debug!("Rejected request with malformed Basic auth credentials");
+    }
+    Some(password.ok_or(AuthError::Invalid))
+}
+
+/// Request paths that don't require an api-key, such as health and readiness probes
+#[derive(Clone, Debug, Default)]
+pub struct UnauthenticatedPaths {
+    paths: Vec<UnauthenticatedPathConfig>,
+}
+
+impl UnauthenticatedPaths {
+    pub fn new(paths: Vec<UnauthenticatedPathConfig>) -> Self {
+        Self { paths }
+    }
+
+    /// Check whether the request path may skip authentication.
+    pub fn contains(&self, path: &str) -> bool {
+        self.paths.iter().any(|unauthenticated| {
+            if unauthenticated.prefix {
+                path.starts_with(&unauthenticated.path)
+            } else {
+                path == unauthenticated.path
+            }
+        })
+    }
+}
+
+/// Operation class of a request, which an api-key must permit
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    /// Request only reads data
+    Read,
+    /// Request may modify points
+    Write,
+    /// Request manages collections, aliases, snapshots or the cluster
+    Admin,
+}
+
+/// Api-keys granting full access, api-keys granting read-only access, and scoped api-keys
+/// granting the access classes they are configured with
+///
+/// Requests signed with the shared secret of the request signer are granted full access too, as
+/// are the keys loaded from the api-key file. Shared between the REST and gRPC api-key
+/// middlewares.
+#[derive(Clone, Debug, Default)]
+pub struct AuthKeys {
+    read_write: ApiKeys,
+    /// Read-write keys loaded from the api-key file, replaced whenever the file changes
+    key_file: ApiKeys,
+    read_only: ApiKeys,
+    scoped: ApiKeys,
+    /// Permissions of each scoped key, in the same order as `scoped`
+    scope_permissions: Vec<Vec<Access>>,
+    request_signer: Option<RequestSigner>,
+    jwt: Option<JwtVerifier>,
+}
+
+/// Key a request was authenticated with, inserted into the request extensions
+///
+/// Lets request handlers apply per-tenant logic without seeing the key itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthContext {
+    pub key_label: String,
+    /// Whether the key only grants read access
+    pub is_read_only: bool,
+}
+
+impl AuthKeys {
+    pub fn new(read_write: ApiKeys, read_only: ApiKeys) -> Self {
+        Self {
+            read_write,
+            key_file: ApiKeys::default(),
+            read_only,
+            scoped: ApiKeys::default(),
+            scope_permissions: Vec::new(),
+            request_signer: None,
+            jwt: None,
+        }
+    }
+
+    /// Collect all api-keys from the service configuration.
+    ///
+    /// Returns `None` if no api-key is configured, in which case authentication is disabled.
+    pub fn from_config(service_config: &ServiceConfig) -> io::Result<Option<Self>> {
+        let read_write: Vec<_> = service_config
+            .api_key
+            .iter()
+            .cloned()
+            .map(ApiKeyConfig::Key)
+            .chain(service_config.api_keys.iter().cloned())
+            .collect();
+        let read_only: Vec<_> = service_config
+            .read_only_api_key
+            .iter()
+            .cloned()
+            .map(ApiKeyConfig::Key)
+            .chain(service_config.read_only_api_keys.iter().cloned())
+            .collect();
+        let scoped: Vec<_> = service_config
+            .scoped_api_keys
+            .iter()
+            .map(ScopedApiKeyConfig::api_key)
+            .collect();
+
+        let hashed = service_config.api_key_hashed;
+        let auth_keys = Self {
+            read_write: ApiKeys::from_config(&read_write, hashed)?,
+            // Loaded separately, see `KeyFile`
+            key_file: ApiKeys::default(),
+            read_only: ApiKeys::from_config(&read_only, hashed)?,
+            scoped: ApiKeys::from_config(&scoped, hashed)?,
+            scope_permissions: service_config
+                .scoped_api_keys
+                .iter()
+                .map(|config| config.permissions.clone())
+                .collect(),
+            request_signer: service_config.request_signing.as_ref().map(|config| {
+                RequestSigner::new(config, service_config.max_request_size_mb * 1024 * 1024)
+            }),
+            jwt: service_config
+                .jwt
+                .as_ref()
+                .map(JwtVerifier::new)
+                .transpose()?,
+        };
+
+        if auth_keys.read_write.is_empty()
+            && auth_keys.read_only.is_empty()
+            && auth_keys.scoped.is_empty()
+            && auth_keys.request_signer.is_none()
+            && auth_keys.jwt.is_none()
+            && service_config.api_key_file.is_none()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(auth_keys))
+    }
+
+    /// Signer to verify signed requests with, if request signing is enabled
+    pub fn request_signer(&self) -> Option<&RequestSigner> {
+        self.request_signer.as_ref()
+    }
+
+    /// Authenticate a signed request, which is granted full access.
+    ///
+    /// All signed requests share a single rate limit bucket, as all signers share the secret.
+    pub fn verify_signature(
+        &self,
+        request: &SignedRequest,
+    ) -> Result<(KeyId, RateLimitKey), AuthError> {
+        match &self.request_signer {
+            Some(signer) if signer.verify(request, SystemTime::now()) => {
+                Ok((KeyId::Signed, RateLimitKey::Signed))
+            }
+            _ => {
+                log::debug!("Rejected request with invalid signature");
+                Err(AuthError::Invalid)
+            }
+        }
+    }
+
+    /// Bearer token of the request to verify as JWT, if JWT verification is enabled
+    ///
+    /// Bearer tokens that don't have the shape of a JWT are left to the api-key check.
+    pub fn jwt_bearer_token<'a>(&self, authorization: Option<&'a HeaderValue>) -> Option<&'a str> {
+        self.jwt.as_ref()?;
+        let token = bearer_token(authorization?.to_str().ok()?)?;
+        if jwt::is_jwt(token) {
+            Some(token)
+        } else {
+            None
+        }
+    }
+
+    /// Authenticate a JWT bearer token for the required access.
+    ///
+    /// Tokens are rate limited by their subject, or each on its own if they have none.
+    pub fn verify_jwt(
+        &self,
+        token: &str,
+        access: Access,
+    ) -> Result<(KeyId, RateLimitKey), AuthError> {
+        let granted = match &self.jwt {
+            Some(jwt) => jwt.verify(token, SystemTime::now()),
+            None => None,
+        };
+        auth_trace!(
+            "jwt verified: {}, required access {access:?}",
+            granted.is_some()
+        );
+        match granted {
+            Some(granted) if granted.access.contains(&access) => {
+                let key_id = KeyId::Jwt {
+                    read_only: granted.access.iter().all(|access| *access == Access::Read),
+                };
+                let rate_limit_key = match granted.subject {
+                    Some(subject) => RateLimitKey::Subject(subject),
+                    None => RateLimitKey::digest(token),
+                };
+                Ok((key_id, rate_limit_key))
+            }
+            // Rejected as unauthenticated like any other invalid token
+            Some(_) => {
+                log::debug!("Rejected request with JWT lacking {access:?} permission");
+                Err(AuthError::InvalidToken)
+            }
+            None => Err(AuthError::InvalidToken),
+        }
+    }
+
+    /// Authenticate the given key for the required access.
+    ///
+    /// Returns which configured key matched. The key is always compared against read-write,
+    /// read-only and scoped keys, so the timing doesn't reveal which kind of key matched. Expiry
+    /// and permissions are only checked after comparing, for the same reason.
+    pub fn authenticate(&self, key: &str, access: Access) -> Result<KeyId, AuthError> {
+        self.authenticate_at(key, access, SystemTime::now())
+    }
+
+    fn authenticate_at(
+        &self,
+        key: &str,
+        access: Access,
+        now: SystemTime,
+    ) -> Result<KeyId, AuthError> {
+        let read_write = self.read_write.find(key);
+        let key_file = self.key_file.find(key);
+        let read_only = self.read_only.find(key);
+        let scoped = self.scoped.find(key);
+
+        auth_trace!(
+            "api-key matched read-write: {}, key file: {}, read-only: {}, scoped: {}, \
+             required access {access:?}",
+            read_write.is_some(),
+            key_file.is_some(),
+            read_only.is_some(),
+            scoped.is_some(),
+        );
+
+        let (key_id, expired) = match (read_write, key_file, read_only, scoped) {
+            (Some(index), _, _, _) => (
+                KeyId::ReadWrite(index),
+                self.read_write.is_expired(index, now),
+            ),
+            (None, Some(index), _, _) => (KeyId::File(index), false),
+            (None, None, Some(index), _) if access == Access::Read => (
+                KeyId::ReadOnly(index),
+                self.read_only.is_expired(index, now),
+            ),
+            (None, None, _, Some(index)) if self.scope_permissions[index].contains(&access) => {
+                (KeyId::Scoped(index), self.scoped.is_expired(index, now))
+            }
+            (None, None, _, Some(index)) => {
+                log::debug!(
+                    "Rejected request with api-key {:?} lacking {access:?} permission",
+                    self.key_label(KeyId::Scoped(index)),
+                );
+                return Err(AuthError::Invalid);
+            }
+            _ => {
+                log::debug!("Rejected request with invalid api-key");
+                return Err(AuthError::Invalid);
+            }
+        };
+
+        if expired {
+            log::debug!("Rejected request with expired api-key {key_id:?}");
+            return Err(AuthError::Expired(key_id));
+        }
+
+        auth_trace!("authenticated as {key_id:?}");
+        Ok(key_id)
+    }
+
+    /// Compare a placeholder against all configured keys, for a request without an api-key.
+    ///
+    /// The placeholder is compared just like a wrong key would be, so the timing of rejecting a
+    /// request doesn't reveal whether it had an api-key at all.
+    pub fn compare_missing_key(&self) {
+        for keys in [
+            &self.read_write,
+            &self.key_file,
+            &self.read_only,
+            &self.scoped,
+        ] {
+            std::hint::black_box(keys.find(MISSING_KEY_PLACEHOLDER));
+        }
+    }
+
+    /// Name of the given key for logging, never the key itself
+    ///
+    /// This is the configured label of the key, or its kind and position if it has none.
+    pub fn key_label(&self, key_id: KeyId) -> String {
+        let (keys, index, kind) = match key_id {
+            KeyId::ReadWrite(index) => (&self.read_write, index, "read-write"),
+            KeyId::ReadOnly(index) => (&self.read_only, index, "read-only"),
+            KeyId::Scoped(index) => (&self.scoped, index, "scoped"),
+            KeyId::File(index) => (&self.key_file, index, "key file"),
+            KeyId::Signed => return "signed request".to_string(),
+            KeyId::Jwt { .. } => return "jwt".to_string(),
+        };
+        match keys.label(index) {
+            Some(label) => label.to_string(),
+            None => format!("{kind} #{index}"),
+        }
+    }
+
+    /// Context of a successfully authenticated request, for request handlers
+    pub fn auth_context(&self, key_id: KeyId) -> AuthContext {
+        let is_read_only = match key_id {
+            KeyId::ReadWrite(_) | KeyId::File(_) | KeyId::Signed => false,
+            KeyId::ReadOnly(_) => true,
+            KeyId::Jwt { read_only } => read_only,
+            KeyId::Scoped(index) => self.scope_permissions[index]
+                .iter()
+                .all(|access| *access == Access::Read),
+        };
+        AuthContext {
+            key_label: self.key_label(key_id),
+            is_read_only,
+        }
+    }
+}
+
+/// Request details logged with the outcome of its api-key check
+pub struct AuthLogContext<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub peer: Option<IpAddr>,
+    /// Api-key sent with the request, only ever logged redacted
+    pub key: Option<&'a str>,
+    pub request_id: &'a RequestId,
+}
+
+impl AuthLogContext<'_> {
+    /// Log the outcome of the api-key check, for auditing.
+    ///
+    /// Rejections are logged as warnings, with the client address, the request and a redacted
+    /// api-key. Successful requests are logged at debug level, with the label of the matched key.
+    /// Both include the request id, to trace a request across proxies and Qdrant.
+    /// `auth_keys` must be the keys the request was authenticated with.
+    pub fn log(&self, outcome: AuthOutcome, auth_keys: &AuthKeys) {
+        let reason = match outcome {
+            AuthOutcome::Ok(key_id) => {
+                log::debug!(
+                    "Authenticated {} {} (request id {}) with api-key {}",
+                    self.method,
+                    self.path,
+                    self.request_id,
+                    auth_keys.key_label(key_id),
+                );
+                return;
+            }
+            AuthOutcome::Unauthorized => "missing api-key",
+            AuthOutcome::Forbidden => "invalid api-key",
+            AuthOutcome::Expired => "expired api-key",
+            AuthOutcome::RateLimited => "rate limit exceeded",
+            AuthOutcome::AddressNotAllowed => "address not allowed",
+            AuthOutcome::InvalidToken => "invalid token",
+        };
+        let peer = match self.peer {
+            Some(peer) => peer.to_string(),
+            None => "unknown address".to_string(),
+        };
+        let key = match self.key {
+            Some(key) => RedactedKey(key).to_string(),
+            None => "none".to_string(),
+        };
+        log::warn!(
+            "Rejected {} {} from {peer} (request id {}): {reason}, api-key: {key}",
+            self.method,
+            self.path,
+            self.request_id,
+        );
+    }
+}
+
+/// Api-key shortened for logging to its first few characters and its length
+///
+/// At most half of the key is shown, so short keys are never logged in full.
+struct RedactedKey<'a>(&'a str);
+
+impl fmt::Display for RedactedKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.0.chars().count();
+        let prefix: String = self.0.chars().take(4.min(len / 2)).collect();
+        write!(f, "{prefix}... ({len} chars)")
+    }
+}
+
+/// Compared against the configured keys in place of a missing api-key
+const MISSING_KEY_PLACEHOLDER: &str = "00000000000000000000000000000000";
+
+/// Shared handle to the current api-keys, which may be replaced while the server is running
+///
+/// Cloned into the REST and gRPC middlewares, which load the current keys for each request.
+#[derive(Clone, Debug, Default)]
+pub struct AuthKeysHandle {
+    auth_keys: Arc<ArcSwap<AuthKeys>>,
+}
+
+impl AuthKeysHandle {
+    pub fn new(auth_keys: AuthKeys) -> Self {
+        Self {
+            auth_keys: Arc::new(ArcSwap::from_pointee(auth_keys)),
+        }
+    }
+
+    /// Current api-keys. Requests in flight keep the keys they loaded, even if replaced meanwhile.
+    pub fn load(&self) -> Guard<Arc<AuthKeys>> {
+        self.auth_keys.load()
+    }
+
+    /// Current api-keys, to hold on to across await points.
+    pub fn load_full(&self) -> Arc<AuthKeys> {
+        self.auth_keys.load_full()
+    }
+
+    /// Atomically replace all api-keys. Following requests only accept the new keys.
+    ///
+    /// Scoped keys and request signing stay as they are.
+    pub fn update_keys(&self, read_write: ApiKeys, read_only: ApiKeys) {
+        let current = self.load();
+        self.auth_keys.store(Arc::new(AuthKeys {
+            read_write,
+            key_file: current.key_file.clone(),
+            read_only,
+            scoped: current.scoped.clone(),
+            scope_permissions: current.scope_permissions.clone(),
+            request_signer: current.request_signer.clone(),
+            jwt: current.jwt.clone(),
+        }));
+    }
+
+    /// Atomically replace the keys loaded from the api-key file. Other keys stay as they are.
+    pub fn update_key_file(&self, key_file: ApiKeys) {
+        let current = self.load();
+        self.auth_keys.store(Arc::new(AuthKeys {
+            key_file,
+            ..AuthKeys::clone(&current)
+        }));
+    }
+
+    /// Reload the api-keys from the configuration.
+    ///
+    /// Authentication can't be enabled or disabled at runtime, so at least one api-key must
+    /// remain configured. Keys loaded from the api-key file are kept, the file is watched
+    /// separately.
+    pub fn reload(&self, config_path: Option<String>) -> anyhow::Result<()> {
+        let settings = Settings::new(config_path)?;
+        match AuthKeys::from_config(&settings.service)? {
+            Some(mut auth_keys) => {
+                auth_keys.key_file = self.load().key_file.clone();
+                self.auth_keys.store(Arc::new(auth_keys));
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!(
+                "no api-key configured, authentication can't be disabled at runtime"
+            )),
+        }
+    }
+}
+
+/// Reload the api-keys from the configuration whenever the process receives SIGHUP.
+#[cfg(unix)]
+pub async fn reload_on_sighup(auth_keys: AuthKeysHandle, config_path: Option<String>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            log::error!("Failed to listen for SIGHUP, api-keys can't be reloaded: {err}");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        match auth_keys.reload(config_path.clone()) {
+            Ok(()) => log::info!("Reloaded api-keys"),
+            Err(err) => log::error!("Failed to reload api-keys, keeping current ones: {err}"),
+        }
+    }
+}
+
+/// How often to check the api-key file for changes
+const KEY_FILE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// File with read-write api-keys, one per line, that may be replaced while the server is running
+///
+/// This is how secret management tooling typically rotates credentials on disk. Empty lines and
+/// lines starting with `#` are ignored. Keys are hex encoded SHA-256 digests if `hashed` is set,
+/// like the configured keys.
+#[derive(Debug)]
+pub struct KeyFile {
+    path: PathBuf,
+    hashed: bool,
+    /// Contents read from the file the last time, or why it couldn't be read
+    last_read: Option<Result<String, io::ErrorKind>>,
+}
+
+impl KeyFile {
+    pub fn new(path: impl Into<PathBuf>, hashed: bool) -> Self {
+        Self {
+            path: path.into(),
+            hashed,
+            last_read: None,
+        }
+    }
+
+    /// Load the keys into `auth_keys` if the file changed since the last call.
+    ///
+    /// If the file is missing, empty or malformed, the current keys are kept and a warning is
+    /// logged, rather than locking out every client of the file.
+    pub fn reload_if_changed(&mut self, auth_keys: &AuthKeysHandle) {
+        let contents = fs::read_to_string(&self.path).map_err(|err| err.kind());
+        if self.last_read.as_ref() == Some(&contents) {
+            return;
+        }
+
+        let keys = match &contents {
+            Ok(contents) => parse_key_file(contents, self.hashed),
+            Err(kind) => Err(io::Error::from(*kind)),
+        };
+        self.last_read = Some(contents);
+
+        match keys {
+            Ok(keys) => {
+                auth_keys.update_key_file(keys);
+                log::info!("Loaded api-keys from {}", self.path.display());
+            }
+            Err(err) => log::warn!(
+                "Failed to load api-keys from {}, keeping current keys: {err}",
+                self.path.display(),
+            ),
+        }
+    }
+}
+
+/// Api-keys listed in the contents of a key file, one per line
+fn parse_key_file(contents: &str, hashed: bool) -> io::Result<ApiKeys> {
+    let keys: Vec<_> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|key| ApiKeyConfig::Key(key.to_string()))
+        .collect();
+    if keys.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "api-key file has no keys",
+        ));
+    }
+    ApiKeys::from_config(&keys, hashed)
+}
+
+/// Reload the api-keys from the key file whenever it changes.
+pub async fn watch_key_file(auth_keys: AuthKeysHandle, mut key_file: KeyFile) {
+    let mut interval = tokio::time::interval(KEY_FILE_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        key_file.reload_if_changed(&auth_keys);
+    }
+}
+
+/// Identifies the configured api-key a request was authenticated with
+///
+/// Holds the index of the key in the configuration, never the key itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KeyId {
+    ReadWrite(usize),
+    ReadOnly(usize),
+    Scoped(usize),
+    /// Read-write key loaded from the api-key file
+    File(usize),
+    /// Signed with the shared secret instead of sending an api-key
+    Signed,
+    /// JWT bearer token, granting only read access if `read_only`
+    Jwt {
+        read_only: bool,
+    },
+}
+
+/// Client a request is rate limited as
+///
+/// Unlike [`KeyId`], this doesn't depend on the position of a key in the configuration, so clients
+/// keep their bucket when the api-keys are reloaded. Never holds the credentials themselves.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// SHA-256 digest of an api-key, or of a JWT without subject
+    Digest([u8; digest::SHA256_OUTPUT_LEN]),
+    /// `sub` claim of a JWT
+    Subject(String),
+    /// Any signed request, which can't tell signers apart as they share the secret
+    Signed,
+}
+
+impl RateLimitKey {
+    /// Rate limit by the digest of the given api-key or token.
+    pub fn digest(credential: &str) -> Self {
+        let mut bytes = [0; digest::SHA256_OUTPUT_LEN];
+        bytes.copy_from_slice(digest::digest(&digest::SHA256, credential.as_bytes()).as_ref());
+        Self::Digest(bytes)
+    }
+}
+
+/// Reason for rejecting an api-key
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AuthError {
+    /// Doesn't match any configured key granting the required access
+    Invalid,
+    /// Matches a configured key that has expired
+    Expired(KeyId),
+    /// JWT bearer token with an invalid signature or claims, or lacking the required access
+    InvalidToken,
+}
+
+/// Outcome of the api-key check of a single request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AuthOutcome {
+    /// Authenticated with the given key
+    Ok(KeyId),
+    /// No api-key given
+    Unauthorized,
+    /// Api-key given, but it doesn't grant the required access
+    Forbidden,
+    /// Api-key given, but it has expired
+    Expired,
+    /// Authenticated, but the rate limit of the key is exhausted
+    RateLimited,
+    /// Modifying request from an address outside of the ip allowlist
+    AddressNotAllowed,
+    /// JWT bearer token given, but it is invalid, expired or lacks the required access
+    InvalidToken,
+}
+
+impl AuthOutcome {
+    /// Outcome of a request authenticated with the given key, subject to its rate limit.
+    ///
+    /// Rate limiting only happens after successful authentication, so anonymous requests can't
+    /// exhaust the buckets of valid keys.
+    pub fn authenticated(
+        key_id: KeyId,
+        rate_limit_key: RateLimitKey,
+        rate_limiter: Option<&RateLimiter<RateLimitKey>>,
+    ) -> Self {
+        let allowed = match rate_limiter {
+            Some(rate_limiter) => rate_limiter.try_acquire(rate_limit_key),
+            None => true,
+        };
+        if allowed {
+            AuthOutcome::Ok(key_id)
+        } else {
+            AuthOutcome::RateLimited
+        }
+    }
+}
+
+impl From<AuthError> for AuthOutcome {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::Invalid => AuthOutcome::Forbidden,
+            AuthError::Expired(_) => AuthOutcome::Expired,
+            AuthError::InvalidToken => AuthOutcome::InvalidToken,
+        }
+    }
+}
+
+/// Authentication outcomes of both REST and gRPC requests, exposed in the metrics output
+pub static AUTH_METRICS: AuthMetrics = AuthMetrics::new();
+
+/// Counters of authentication outcomes
+#[derive(Debug, Default)]
+pub struct AuthMetrics {
+    ok_read_write: AtomicU64,
+    ok_read_only: AtomicU64,
+    ok_scoped: AtomicU64,
+    ok_signed: AtomicU64,
+    ok_jwt: AtomicU64,
+    unauthorized: AtomicU64,
+    forbidden: AtomicU64,
+    expired: AtomicU64,
+    rate_limited: AtomicU64,
+    address_not_allowed: AtomicU64,
+    invalid_token: AtomicU64,
+}
+
+impl AuthMetrics {
+    pub const fn new() -> Self {
+        Self {
+            ok_read_write: AtomicU64::new(0),
+            ok_read_only: AtomicU64::new(0),
+            ok_scoped: AtomicU64::new(0),
+            ok_signed: AtomicU64::new(0),
+            ok_jwt: AtomicU64::new(0),
+            unauthorized: AtomicU64::new(0),
+            forbidden: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            address_not_allowed: AtomicU64::new(0),
+            invalid_token: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, outcome: AuthOutcome) {
+        let counter = match outcome {
+            AuthOutcome::Ok(KeyId::ReadWrite(_) | KeyId::File(_)) => &self.ok_read_write,
+            AuthOutcome::Ok(KeyId::ReadOnly(_)) => &self.ok_read_only,
+            AuthOutcome::Ok(KeyId::Scoped(_)) => &self.ok_scoped,
+            AuthOutcome::Ok(KeyId::Signed) => &self.ok_signed,
+            AuthOutcome::Ok(KeyId::Jwt { .. }) => &self.ok_jwt,
+            AuthOutcome::Unauthorized => &self.unauthorized,
+            AuthOutcome::Forbidden => &self.forbidden,
+            AuthOutcome::Expired => &self.expired,
+            AuthOutcome::RateLimited => &self.rate_limited,
+            AuthOutcome::AddressNotAllowed => &self.address_not_allowed,
+            AuthOutcome::InvalidToken => &self.invalid_token,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current counts, with their `result` and `key` labels.
+    pub fn counts(&self) -> [(&'static str, Option<&'static str>, u64); 11] {
+        [
+            ("ok", Some("read_write"), &self.ok_read_write),
+            ("ok", Some("read_only"), &self.ok_read_only),
+            ("ok", Some("scoped"), &self.ok_scoped),
+            ("ok", Some("signed"), &self.ok_signed),
+            ("ok", Some("jwt"), &self.ok_jwt),
+            ("unauthorized", None, &self.unauthorized),
+            ("forbidden", None, &self.forbidden),
+            ("expired", None, &self.expired),
+            ("rate_limited", None, &self.rate_limited),
+            ("address_not_allowed", None, &self.address_not_allowed),
+            ("invalid_token", None, &self.invalid_token),
+        ]
+        .map(|(result, key, counter)| (result, key, counter.load(Ordering::Relaxed)))
+    }
+}
+
+/// Set of api-keys a request may authenticate with
+#[derive(Clone, Debug, Default)]
+pub struct ApiKeys {
+    keys: Vec<ApiKey>,
+    /// Whether keys are SHA-256 digests instead of plaintext keys
+    hashed: bool,
+}
+
+#[derive(Clone, Debug)]
+struct ApiKey {
+    /// Expected key, or its SHA-256 digest if hashed
+    key: Vec<u8>,
+    label: Option<String>,
+    expires_at: Option<SystemTime>,
+}
+
+impl ApiKeys {
+    /// Plaintext api-keys, that never expire.
+    pub fn new(keys: Vec<String>) -> Self {
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|key| ApiKey {
+                    key: key.into_bytes(),
+                    label: None,
+                    expires_at: None,
+                })
+                .collect(),
+            hashed: false,
+        }
+    }
+
+    /// Api-keys given as hex encoded SHA-256 digests, that never expire.
+    ///
+    /// The plaintext keys are never kept in memory. Incoming keys are hashed before comparing.
+    pub fn from_hex_digests(digests: &[String]) -> io::Result<Self> {
+        let keys = digests
+            .iter()
+            .map(|digest| {
+                Ok(ApiKey {
+                    key: decode_hex_digest(digest)?,
+                    label: None,
+                    expires_at: None,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { keys, hashed: true })
+    }
+
+    /// Configured api-keys, given as hex encoded SHA-256 digests if `hashed` is set.
+    fn from_config(keys: &[ApiKeyConfig], hashed: bool) -> io::Result<Self> {
+        let keys = keys
+            .iter()
+            .map(|config| {
+                let key = if hashed {
+                    decode_hex_digest(config.key())?
+                } else {
+                    config.key().as_bytes().to_vec()
+                };
+                Ok(ApiKey {
+                    key,
+                    label: config.label().map(str::to_string),
+                    expires_at: config.expires_at().map(SystemTime::from),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { keys, hashed })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Find the index of the configured key matching the given key.
+    ///
+    /// Each configured key is compared in constant time. We always compare against all keys and
+    /// never stop at the first match, so the timing doesn't reveal which key matched.
+    pub fn find(&self, key: &str) -> Option<usize> {
+        if self.hashed {
+            let digest = digest::digest(&digest::SHA256, key.as_bytes());
+            self.find_bytes(digest.as_ref())
+        } else {
+            self.find_bytes(key.as_bytes())
+        }
+    }
+
+    fn find_bytes(&self, key: &[u8]) -> Option<usize> {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(None, |matched, (index, expected)| {
+                if constant_time_eq(&expected.key, key) {
+                    Some(index)
+                } else {
+                    matched
+                }
+            })
+    }
+
+    fn label(&self, index: usize) -> Option<&str> {
+        self.keys[index].label.as_deref()
+    }
+
+    fn is_expired(&self, index: usize, now: SystemTime) -> bool {
+        match self.keys[index].expires_at {
+            Some(expires_at) => expires_at <= now,
+            None => false,
+        }
+    }
+}
+
+fn decode_hex_digest(digest: &str) -> io::Result<Vec<u8>> {
+    match hex::decode(digest.trim()) {
+        Ok(bytes) if bytes.len() == digest::SHA256_OUTPUT_LEN => Ok(bytes),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "hashed api-key must be a hex encoded SHA-256 digest",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine as _;
+    use ring::hmac;
+
+    use super::*;
+    use crate::settings::{JwtAlgorithm, JwtConfig, RateLimitConfig};
+
+    #[test]
+    fn test_matches_any_key() {
+        let keys = ApiKeys::new(vec!["first".into(), "second".into(), "third".into()]);
+        assert_eq!(keys.find("first"), Some(0));
+        assert_eq!(keys.find("second"), Some(1));
+        assert_eq!(keys.find("third"), Some(2));
+        assert_eq!(keys.find("fourth"), None);
+        assert_eq!(keys.find(""), None);
+    }
+
+    #[test]
+    fn test_matches_nothing_without_keys() {
+        assert_eq!(ApiKeys::default().find(""), None);
+        assert_eq!(ApiKeys::default().find("key"), None);
+    }
+
+    #[test]
+    fn test_matches_hashed_key() {
+        // SHA-256 of `secret`
+        let digest = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+        let keys = ApiKeys::from_hex_digests(&[digest.into()]).unwrap();
+        assert_eq!(keys.find("secret"), Some(0));
+        assert_eq!(keys.find("Secret"), None);
+        assert_eq!(keys.find(digest), None);
+    }
+
+    #[test]
+    fn test_read_only_key() {
+        let auth_keys = AuthKeys::new(
+            ApiKeys::new(vec!["read-write".into()]),
+            ApiKeys::new(vec!["read-only".into()]),
+        );
+        assert_eq!(
+            auth_keys.authenticate("read-write", Access::Read),
+            Ok(KeyId::ReadWrite(0)),
+        );
+        assert_eq!(
+            auth_keys.authenticate("read-write", Access::Write),
+            Ok(KeyId::ReadWrite(0)),
+        );
+        assert_eq!(
+            auth_keys.authenticate("read-only", Access::Read),
+            Ok(KeyId::ReadOnly(0)),
+        );
+        assert_eq!(
+            auth_keys.authenticate("read-only", Access::Write),
+            Err(AuthError::Invalid)
+        );
+        assert_eq!(
+            auth_keys.authenticate("other", Access::Read),
+            Err(AuthError::Invalid)
+        );
+        assert_eq!(
+            auth_keys.authenticate("other", Access::Write),
+            Err(AuthError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_multiple_read_only_keys() {
+        let auth_keys = AuthKeys::new(
+            ApiKeys::new(vec!["read-write".into()]),
+            ApiKeys::new(vec!["first".into(), "second".into()]),
+        );
+        assert_eq!(
+            auth_keys.authenticate("first", Access::Read),
+            Ok(KeyId::ReadOnly(0)),
+        );
+        assert_eq!(
+            auth_keys.authenticate("second", Access::Read),
+            Ok(KeyId::ReadOnly(1)),
+        );
+        assert_eq!(
+            auth_keys.authenticate("second", Access::Write),
+            Err(AuthError::Invalid),
+        );
+
+        // Revoking one read-only key keeps the others
+        let handle = AuthKeysHandle::new(auth_keys);
+        handle.update_keys(
+            ApiKeys::new(vec!["read-write".into()]),
+            ApiKeys::new(vec!["second".into()]),
+        );
+        let current = handle.load();
+        assert_eq!(
+            current.authenticate("first", Access::Read),
+            Err(AuthError::Invalid),
+        );
+        assert_eq!(
+            current.authenticate("second", Access::Read),
+            Ok(KeyId::ReadOnly(0)),
+        );
+    }
+
+    #[test]
+    fn test_scoped_key() {
+        let scoped = |key: &str, label: &str, permissions: Vec<Access>| ScopedApiKeyConfig {
+            key: key.into(),
+            label: label.into(),
+            permissions,
+            expires_at: None,
+        };
+        let configs = [
+            scoped("search", "search-only", vec![Access::Read]),
+            scoped("ingest", "ingest", vec![Access::Read, Access::Write]),
+            scoped("admin", "admin", vec![Access::Admin]),
+        ];
+        let keys: Vec<_> = configs.iter().map(ScopedApiKeyConfig::api_key).collect();
+        let auth_keys = AuthKeys {
+            scoped: ApiKeys::from_config(&keys, false).unwrap(),
+            scope_permissions: configs
+                .iter()
+                .map(|config| config.permissions.clone())
+                .collect(),
+            ..AuthKeys::new(ApiKeys::new(vec!["master".into()]), ApiKeys::default())
+        };
+
+        let check = |key, access| auth_keys.authenticate(key, access);
+        assert_eq!(check("search", Access::Read), Ok(KeyId::Scoped(0)));
+        assert_eq!(check("search", Access::Write), Err(AuthError::Invalid));
+        assert_eq!(check("search", Access::Admin), Err(AuthError::Invalid));
+        assert_eq!(check("ingest", Access::Read), Ok(KeyId::Scoped(1)));
+        assert_eq!(check("ingest", Access::Write), Ok(KeyId::Scoped(1)));
+        assert_eq!(check("ingest", Access::Admin), Err(AuthError::Invalid));
+        assert_eq!(check("admin", Access::Read), Err(AuthError::Invalid));
+        assert_eq!(check("admin", Access::Admin), Ok(KeyId::Scoped(2)));
+
+        // The master key keeps granting everything
+        assert_eq!(check("master", Access::Admin), Ok(KeyId::ReadWrite(0)));
+    }
+
+    #[test]
+    fn test_auth_context() {
+        let labeled = |key: &str, label: &str| ApiKeyConfig::Detailed {
+            key: key.into(),
+            label: Some(label.into()),
+            expires_at: None,
+        };
+        let read_write = [
+            labeled("tenant-a", "tenant a"),
+            ApiKeyConfig::Key("tenant-b".into()),
+        ];
+        let read_only = [labeled("dashboard", "dashboard")];
+        let scoped = ScopedApiKeyConfig {
+            key: "search".into(),
+            label: "search-only".into(),
+            permissions: vec![Access::Read],
+            expires_at: None,
+        };
+        let auth_keys = AuthKeys {
+            scoped: ApiKeys::from_config(&[scoped.api_key()], false).unwrap(),
+            scope_permissions: vec![scoped.permissions],
+            ..AuthKeys::new(
+                ApiKeys::from_config(&read_write, false).unwrap(),
+                ApiKeys::from_config(&read_only, false).unwrap(),
+            )
+        };
+
+        let context = |key_label: &str, is_read_only| AuthContext {
+            key_label: key_label.into(),
+            is_read_only,
+        };
+        let check = |key| {
+            let key_id = auth_keys.authenticate(key, Access::Read).unwrap();
+            auth_keys.auth_context(key_id)
+        };
+        assert_eq!(check("tenant-a"), context("tenant a", false));
+        assert_eq!(check("tenant-b"), context("read-write #1", false));
+        assert_eq!(check("dashboard"), context("dashboard", true));
+        assert_eq!(check("search"), context("search-only", true));
+        assert_eq!(
+            auth_keys.auth_context(KeyId::Signed),
+            context("signed request", false),
+        );
+    }
+
+    #[test]
+    fn test_expired_key() {
+        let expiring = |key: &str, expires_at: &str| ApiKeyConfig::Detailed {
+            key: key.into(),
+            label: None,
+            expires_at: Some(expires_at.parse().unwrap()),
+        };
+        let read_write = ApiKeys::from_config(
+            &[
+                ApiKeyConfig::Key("forever".into()),
+                expiring("expiring", "2030-01-01T00:00:00Z"),
+            ],
+            false,
+        )
+        .unwrap();
+        let read_only =
+            ApiKeys::from_config(&[expiring("read-only", "2030-01-01T00:00:00Z")], false).unwrap();
+        let auth_keys = AuthKeys::new(read_write, read_only);
+
+        let before: SystemTime = "2029-12-31T23:59:59Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap()
+            .into();
+        let after: SystemTime = "2030-01-01T00:00:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap()
+            .into();
+
+        for now in [before, after] {
+            assert_eq!(
+                auth_keys.authenticate_at("forever", Access::Write, now),
+                Ok(KeyId::ReadWrite(0)),
+            );
+        }
+        assert_eq!(
+            auth_keys.authenticate_at("expiring", Access::Write, before),
+            Ok(KeyId::ReadWrite(1)),
+        );
+        assert_eq!(
+            auth_keys.authenticate_at("expiring", Access::Write, after),
+            Err(AuthError::Expired(KeyId::ReadWrite(1))),
+        );
+        assert_eq!(
+            auth_keys.authenticate_at("read-only", Access::Read, after),
+            Err(AuthError::Expired(KeyId::ReadOnly(0))),
+        );
+        assert_eq!(
+            auth_keys.authenticate_at("other", Access::Write, before),
+            Err(AuthError::Invalid),
+        );
+    }
+
+    #[test]
+    fn test_update_keys() {
+        let handle = AuthKeysHandle::new(AuthKeys::new(
+            ApiKeys::new(vec!["old".into()]),
+            ApiKeys::default(),
+        ));
+        let in_flight = handle.load();
+        assert!(in_flight.authenticate("old", Access::Write).is_ok());
+
+        handle.update_keys(
+            ApiKeys::new(vec!["new".into()]),
+            ApiKeys::new(vec!["new-read-only".into()]),
+        );
+
+        // Requests in flight finish with the keys they loaded
+        assert!(in_flight.authenticate("old", Access::Write).is_ok());
+        assert!(in_flight.authenticate("new", Access::Write).is_err());
+
+        let current = handle.load();
+        assert!(current.authenticate("old", Access::Write).is_err());
+        assert!(current.authenticate("new", Access::Write).is_ok());
+        assert!(current.authenticate("new-read-only", Access::Read).is_ok());
+        assert!(handle
+            .clone()
+            .load()
+            .authenticate("new", Access::Write)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api-keys");
+        let handle = AuthKeysHandle::new(AuthKeys::new(
+            ApiKeys::new(vec!["config".into()]),
+            ApiKeys::default(),
+        ));
+        let mut key_file = KeyFile::new(&path, false);
+        let check = |key| handle.load().authenticate(key, Access::Write);
+
+        // Missing file at startup, only the configured keys are accepted
+        key_file.reload_if_changed(&handle);
+        assert_eq!(check("config"), Ok(KeyId::ReadWrite(0)));
+
+        fs::write(&path, "# comment\nfirst\n\n  second  \n").unwrap();
+        key_file.reload_if_changed(&handle);
+        assert_eq!(check("first"), Ok(KeyId::File(0)));
+        assert_eq!(check("second"), Ok(KeyId::File(1)));
+        assert_eq!(check("# comment"), Err(AuthError::Invalid));
+        assert_eq!(check("config"), Ok(KeyId::ReadWrite(0)));
+        assert_eq!(
+            handle.load().auth_context(KeyId::File(1)),
+            AuthContext {
+                key_label: "key file #1".into(),
+                is_read_only: false,
+            },
+        );
+
+        // Rotated keys replace the previous ones
+        fs::write(&path, "rotated\n").unwrap();
+        key_file.reload_if_changed(&handle);
+        assert_eq!(check("rotated"), Ok(KeyId::File(0)));
+        assert_eq!(check("first"), Err(AuthError::Invalid));
+
+        // Empty or missing files keep the current keys
+        fs::write(&path, "\n# no keys\n").unwrap();
+        key_file.reload_if_changed(&handle);
+        assert_eq!(check("rotated"), Ok(KeyId::File(0)));
+        fs::remove_file(&path).unwrap();
+        key_file.reload_if_changed(&handle);
+        assert_eq!(check("rotated"), Ok(KeyId::File(0)));
+
+        // Replacing the configured keys keeps the keys of the file, and the other way around
+        handle.update_keys(ApiKeys::new(vec!["new".into()]), ApiKeys::default());
+        assert_eq!(check("rotated"), Ok(KeyId::File(0)));
+        fs::write(&path, "recreated").unwrap();
+        key_file.reload_if_changed(&handle);
+        assert_eq!(check("recreated"), Ok(KeyId::File(0)));
+        assert_eq!(check("new"), Ok(KeyId::ReadWrite(0)));
+        assert_eq!(check("config"), Err(AuthError::Invalid));
+    }
+
+    #[test]
+    fn test_parse_hashed_key_file() {
+        // SHA-256 of `secret`
+        let digest = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+        let keys = parse_key_file(&format!("{digest}\n"), true).unwrap();
+        assert_eq!(keys.find("secret"), Some(0));
+        assert!(parse_key_file("secret\n", true).is_err());
+        assert!(parse_key_file("", false).is_err());
+    }
+
+    #[test]
+    fn test_auth_metrics() {
+        let metrics = AuthMetrics::new();
+        metrics.record(AuthOutcome::Ok(KeyId::ReadWrite(0)));
+        metrics.record(AuthOutcome::Ok(KeyId::ReadWrite(1)));
+        metrics.record(AuthOutcome::Ok(KeyId::ReadOnly(0)));
+        metrics.record(AuthOutcome::Forbidden);
+        metrics.record(AuthOutcome::Forbidden);
+        metrics.record(AuthOutcome::Forbidden);
+        assert_eq!(
+            metrics.counts(),
+            [
+                ("ok", Some("read_write"), 2),
+                ("ok", Some("read_only"), 1),
+                ("ok", Some("scoped"), 0),
+                ("ok", Some("signed"), 0),
+                ("ok", Some("jwt"), 0),
+                ("unauthorized", None, 0),
+                ("forbidden", None, 3),
+                ("expired", None, 0),
+                ("rate_limited", None, 0),
+                ("address_not_allowed", None, 0),
+                ("invalid_token", None, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_key() {
+        let rate_limiter = RateLimiter::new(&RateLimitConfig {
+            capacity: 1,
+            refill_per_second: 0.0,
+        });
+        let outcome = |rate_limit_key| {
+            AuthOutcome::authenticated(KeyId::Signed, rate_limit_key, Some(&rate_limiter))
+        };
+
+        // Limited by the api-key itself rather than its position, which changes on reload
+        let first = RateLimitKey::digest("first");
+        assert_eq!(outcome(first.clone()), AuthOutcome::Ok(KeyId::Signed));
+        assert_eq!(outcome(first), AuthOutcome::RateLimited);
+        assert_eq!(
+            outcome(RateLimitKey::digest("second")),
+            AuthOutcome::Ok(KeyId::Signed),
+        );
+
+        // Signed requests share a single bucket
+        assert_eq!(
+            outcome(RateLimitKey::Signed),
+            AuthOutcome::Ok(KeyId::Signed)
+        );
+        assert_eq!(outcome(RateLimitKey::Signed), AuthOutcome::RateLimited);
+    }
+
+    /// Keys accepting HS256 tokens signed with `secret`
+    fn jwt_auth_keys() -> AuthKeys {
+        AuthKeys {
+            jwt: Some(
+                JwtVerifier::new(&JwtConfig {
+                    algorithm: JwtAlgorithm::Hs256,
+                    key: "secret".into(),
+                    audience: None,
+                    issuer: None,
+                })
+                .unwrap(),
+            ),
+            ..AuthKeys::default()
+        }
+    }
+
+    /// HS256 token with the given claims, accepted by `jwt_auth_keys`
+    fn token(claims: &str) -> String {
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256"}"#),
+            URL_SAFE_NO_PAD.encode(claims),
+        );
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        let signature = hmac::sign(&key, signing_input.as_bytes());
+        format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    #[test]
+    fn test_jwt_insufficient_scope() {
+        let auth_keys = jwt_auth_keys();
+        let read = token(r#"{"exp":4102444800,"scope":"read"}"#);
+        assert!(auth_keys.verify_jwt(&read, Access::Read).is_ok());
+
+        // Lacking access is rejected as unauthenticated, not as forbidden
+        for access in [Access::Write, Access::Admin] {
+            let err = auth_keys.verify_jwt(&read, access).unwrap_err();
+            assert_eq!(err, AuthError::InvalidToken, "{access:?}");
+            assert_eq!(AuthOutcome::from(err), AuthOutcome::InvalidToken);
+        }
+    }
+
+    #[test]
+    fn test_jwt_rate_limit_key() {
+        let auth_keys = jwt_auth_keys();
+        let rate_limit_key = |token: &str| auth_keys.verify_jwt(token, Access::Read).unwrap().1;
+
+        // Tokens of the same subject share a bucket, other subjects have their own
+        let first = token(r#"{"exp":4102444800,"sub":"client","scope":"read"}"#);
+        let second = token(r#"{"exp":4102444801,"sub":"client","scope":"read"}"#);
+        let other = token(r#"{"exp":4102444800,"sub":"other","scope":"read"}"#);
+        assert_eq!(
+            rate_limit_key(&first),
+            RateLimitKey::Subject("client".into())
+        );
+        assert_eq!(
+            rate_limit_key(&second),
+            RateLimitKey::Subject("client".into())
+        );
+        assert_eq!(
+            rate_limit_key(&other),
+            RateLimitKey::Subject("other".into())
+        );
+
+        // Tokens without subject are limited each on their own
+        let anonymous = token(r#"{"exp":4102444800,"scope":"read"}"#);
+        assert_eq!(rate_limit_key(&anonymous), RateLimitKey::digest(&anonymous));
+    }
+
+    #[test]
+    fn test_unauthenticated_paths() {
+        let paths = UnauthenticatedPaths::new(vec![
+            UnauthenticatedPathConfig {
+                path: "/healthz".into(),
+                prefix: false,
+            },
+            UnauthenticatedPathConfig {
+                path: "/grpc.health.".into(),
+                prefix: true,
+            },
+        ]);
+        assert!(paths.contains("/healthz"));
+        assert!(!paths.contains("/healthz/extra"));
+        assert!(!paths.contains("/health"));
+        assert!(paths.contains("/grpc.health.v1.Health/Check"));
+        assert!(!paths.contains("/qdrant.Points/Search"));
+        assert!(!UnauthenticatedPaths::default().contains("/healthz"));
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer secret"), Some("secret"));
+        assert_eq!(bearer_token("bearer secret"), Some("secret"));
+        assert_eq!(bearer_token("BEARER  secret "), Some("secret"));
+        assert_eq!(bearer_token("Basic c2VjcmV0"), None);
+        assert_eq!(bearer_token("Bearer"), None);
+        assert_eq!(bearer_token("secret"), None);
+    }
+
+    #[test]
+    fn test_basic_auth_password() {
+        // "user:secret", ":secret" and "user:pass:word"
+        assert_eq!(
+            basic_auth_password("Basic dXNlcjpzZWNyZXQ="),
+            Some(Ok("secret".into())),
+        );
+        assert_eq!(
+            basic_auth_password("basic  OnNlY3JldA== "),
+            Some(Ok("secret".into())),
+        );
+        assert_eq!(
+            basic_auth_password("Basic dXNlcjpwYXNzOndvcmQ="),
+            Some(Ok("pass:word".into())),
+        );
+
+        // Not base64, no colon ("secret"), not UTF-8
+        let malformed = Some(Err(AuthError::Invalid));
+        assert_eq!(basic_auth_password("Basic not base64!"), malformed);
+        assert_eq!(basic_auth_password("Basic c2VjcmV0"), malformed);
+        assert_eq!(basic_auth_password("Basic /w=="), malformed);
+
+        assert_eq!(basic_auth_password("Bearer secret"), None);
+        assert_eq!(basic_auth_password("Basic"), None);
+    }
+
+    #[test]
+    fn test_redacted_key() {
+        assert_eq!(
+            RedactedKey("your_secret_api_key").to_string(),
+            "your... (19 chars)",
+        );
+        assert_eq!(RedactedKey("secret").to_string(), "sec... (6 chars)");
+        assert_eq!(RedactedKey("a").to_string(), "... (1 chars)");
+        assert_eq!(RedactedKey("").to_string(), "... (0 chars)");
+    }
+
+    #[test]
+    fn test_invalid_hashed_key() {
+        assert!(ApiKeys::from_hex_digests(&["not hex".into()]).is_err());
+        assert!(ApiKeys::from_hex_digests(&["abcd".into()]).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::io;
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+use crate::settings::IpAllowlistConfig;
+
+/// Networks that client addresses must belong to
+#[derive(Clone, Debug)]
+pub struct IpAllowlist {
+    networks: Vec<IpNet>,
+    trust_forwarded_for: bool,
+}
+
+impl IpAllowlist {
+    pub fn from_config(config: &IpAllowlistConfig) -> io::Result<Self> {
+        let networks = config
+            .networks
+            .iter()
+            .map(|network| parse_network(network))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            networks,
+            trust_forwarded_for: config.trust_forwarded_for,
+        })
+    }
+
+    /// Check whether the client address belongs to any of the allowed networks.
+    ///
+    /// The client address is the peer address of the connection. If `trust_forwarded_for` is set,
+    /// it is the last address in the `X-Forwarded-For` header instead, which is the one appended
+    /// by the proxy in front of us. Earlier addresses in the header are supplied by the client
+    /// and can't be trusted.
+    pub fn allows(&self, peer_addr: Option<IpAddr>, forwarded_for: Option<&str>) -> bool {
+        let forwarded_addr = if self.trust_forwarded_for {
+            forwarded_for.and_then(last_forwarded_addr)
+        } else {
+            None
+        };
+
+        match forwarded_addr.or(peer_addr) {
+            Some(addr) => {
+                let addr = canonical_addr(addr);
+                self.networks.iter().any(|network| network.contains(&addr))
+            }
+            None => false,
+        }
+    }
+}
+
+/// Parse a network in CIDR notation, or a single address.
+fn parse_network(network: &str) -> io::Result<IpNet> {
+    network
+        .parse::<IpNet>()
+        .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid network in ip allowlist: {network}"),
+            )
+        })
+}
+
+/// Last address of an `X-Forwarded-For` header value.
+fn last_forwarded_addr(forwarded_for: &str) -> Option<IpAddr> {
+    forwarded_for.rsplit(',').next()?.trim().parse().ok()
+}
+
+/// Unwrap IPv4-mapped IPv6 addresses, as seen when listening on a dual-stack socket.
+fn canonical_addr(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => addr,
+        },
+        IpAddr::V4(_) => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(networks: &[&str], trust_forwarded_for: bool) -> IpAllowlist {
+        IpAllowlist::from_config(&IpAllowlistConfig {
+            networks: networks.iter().map(|network| network.to_string()).collect(),
+            trust_forwarded_for,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ip_allowlist() {
+        let allowlist = allowlist(&["10.0.0.0/8", "192.168.1.1", "fd00::/8"], false);
+
+        let allows = |addr: &str| allowlist.allows(Some(addr.parse().unwrap()), None);
+        assert!(allows("10.1.2.3"));
+        assert!(allows("192.168.1.1"));
+        assert!(allows("::ffff:10.1.2.3"));
+        assert!(allows("fd12::1"));
+        assert!(!allows("11.0.0.1"));
+        assert!(!allows("192.168.1.2"));
+        assert!(!allows("fe80::1"));
+        assert!(!allowlist.allows(None, None));
+
+        // Header is ignored unless trusted
+        assert!(!allowlist.allows(Some("11.0.0.1".parse().unwrap()), Some("10.0.0.1")));
+    }
+
+    #[test]
+    fn test_ip_allowlist_forwarded_for() {
+        let allowlist = allowlist(&["10.0.0.0/8"], true);
+        let proxy = Some("172.16.0.1".parse().unwrap());
+
+        assert!(allowlist.allows(proxy, Some("10.0.0.1")));
+        assert!(allowlist.allows(proxy, Some("11.0.0.1, 10.0.0.1")));
+        // Only the address appended by the proxy counts
+        assert!(!allowlist.allows(proxy, Some("10.0.0.1, 11.0.0.1")));
+        // Falls back to the peer address without the header
+        assert!(!allowlist.allows(proxy, None));
+        assert!(allowlist.allows(Some("10.0.0.2".parse().unwrap()), None));
+    }
+
+    #[test]
+    fn test_invalid_network() {
+        assert!(parse_network("10.0.0.0/33").is_err());
+        assert!(parse_network("localhost").is_err());
+        assert!(parse_network("::1/128").is_ok());
+    }
+}
@@ -0,0 +1,348 @@
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine as _;
+use ring::{hmac, signature};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::common::auth::Access;
+use crate::settings::{JwtAlgorithm, JwtConfig};
+
+/// Verifies JWT bearer tokens, as an alternative to sending an api-key
+///
+/// Tokens must be signed with the configured algorithm and key, and must carry an `exp` claim.
+/// The `aud` and `iss` claims are only checked if an audience or issuer is configured.
+///
+/// A token grants the access classes listed in its space separated `scope` claim, such as
+/// `"read write"`. Unknown scopes are ignored.
+#[derive(Clone, Debug)]
+pub struct JwtVerifier {
+    key: VerificationKey,
+    audience: Option<String>,
+    issuer: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+enum VerificationKey {
+    Hs256(hmac::Key),
+    /// DER encoded PKCS#1 RSA public key
+    Rs256(Vec<u8>),
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+}
+
+/// Claims of a valid token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedToken {
+    /// Access classes granted by the `scope` claim
+    pub access: Vec<Access>,
+    /// The `sub` claim, identifying who the token was issued to
+    pub subject: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    exp: u64,
+    aud: Option<Audience>,
+    iss: Option<String>,
+    sub: Option<String>,
+    #[serde(default)]
+    scope: String,
+}
+
+/// The `aud` claim is either a single audience or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::One(one) => one == audience,
+            Audience::Many(many) => many.iter().any(|one| one == audience),
+        }
+    }
+}
+
+impl JwtVerifier {
+    pub fn new(config: &JwtConfig) -> io::Result<Self> {
+        let key = match config.algorithm {
+            JwtAlgorithm::Hs256 => {
+                VerificationKey::Hs256(hmac::Key::new(hmac::HMAC_SHA256, config.key.as_bytes()))
+            }
+            JwtAlgorithm::Rs256 => VerificationKey::Rs256(decode_pem(&config.key)?),
+        };
+
+        Ok(Self {
+            key,
+            audience: config.audience.clone(),
+            issuer: config.issuer.clone(),
+        })
+    }
+
+    /// Check the signature and claims of the token.
+    ///
+    /// Returns the claims of the token, or `None` if the token is invalid.
+    pub fn verify(&self, token: &str, now: SystemTime) -> Option<VerifiedToken> {
+        let (signing_input, signature) = token.rsplit_once('.')?;
+        let (header, claims) = signing_input.split_once('.')?;
+
+        // Only accept the configured algorithm, whatever the token claims to be signed with
+        let header: Header = decode_part(header)?;
+        if header.alg != self.key.algorithm() {
+            log::debug!(
+                "Rejected JWT signed with unexpected algorithm {:?}",
+                header.alg
+            );
+            return None;
+        }
+
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        if !self.key.verify(signing_input.as_bytes(), &signature) {
+            log::debug!("Rejected JWT with invalid signature");
+            return None;
+        }
+
+        let claims: Claims = decode_part(claims)?;
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs())
+            .unwrap_or_default();
+        if claims.exp <= now {
+            log::debug!("Rejected expired JWT");
+            return None;
+        }
+
+        if let Some(audience) = &self.audience {
+            let matches = match &claims.aud {
+                Some(aud) => aud.contains(audience),
+                None => false,
+            };
+            if !matches {
+                log::debug!("Rejected JWT for another audience");
+                return None;
+            }
+        }
+
+        if let Some(issuer) = &self.issuer {
+            if claims.iss.as_deref() != Some(issuer.as_str()) {
+                log::debug!("Rejected JWT from another issuer");
+                return None;
+            }
+        }
+
+        Some(VerifiedToken {
+            access: scope_access(&claims.scope),
+            subject: claims.sub,
+        })
+    }
+}
+
+impl VerificationKey {
+    /// Name of the algorithm in the `alg` header
+    fn algorithm(&self) -> &'static str {
+        match self {
+            VerificationKey::Hs256(_) => "HS256",
+            VerificationKey::Rs256(_) => "RS256",
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            VerificationKey::Hs256(key) => hmac::verify(key, message, signature).is_ok(),
+            VerificationKey::Rs256(public_key) => signature::UnparsedPublicKey::new(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                public_key,
+            )
+            .verify(message, signature)
+            .is_ok(),
+        }
+    }
+}
+
+/// Whether the bearer token has the shape of a JWT, rather than being an api-key
+pub fn is_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+/// Decode a base64url encoded JSON part of a token.
+fn decode_part<T: DeserializeOwned>(part: &str) -> Option<T> {
+    let json = URL_SAFE_NO_PAD.decode(part).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Access classes listed in the `scope` claim
+fn scope_access(scope: &str) -> Vec<Access> {
+    scope
+        .split_whitespace()
+        .filter_map(|scope| match scope {
+            "read" => Some(Access::Read),
+            "write" => Some(Access::Write),
+            "admin" => Some(Access::Admin),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Decode a PEM encoded key into DER.
+fn decode_pem(pem: &str) -> io::Result<Vec<u8>> {
+    let base64: String = pem
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    STANDARD.decode(base64).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid JWT public key: {err}"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const RSA_PUBLIC_KEY: &str = "-----BEGIN RSA PUBLIC KEY-----
+MIIBCgKCAQEAubBp6fLGoSYGg24W0Cz9jW0xvEOybY1p5RwayCE3Iujtmki/+pxH
+BOwi66pUpj8mqk+Ko/46KUkCyhrLYlAJ1J6gPbFqHdRI83caHq+060tFJQir98eT
+TLSp+PCBPBSXhLlC/wtLfOrBwl06092yPY+IFulioG3WdxGjkkx/icniWem7UujP
+ab/MUCHgMB/ueOs0DrmnRgqvX6KI0X5/xThEygcNt5txKlqN9mv4SUG8JESh8yqd
+LgRDiyLyulBK4ymOO3A6uIBwBWeh4nU3N9pZqQoPq3lmXG0hpGvSxl4ki/OT1u9g
+6TwMZRDyyh26cdO3GvkcQKVQJ8mr4DwJiwIDAQAB
+-----END RSA PUBLIC KEY-----";
+
+    /// Signed with the private key of `RSA_PUBLIC_KEY`, with claims
+    /// `{"exp":4102444800,"scope":"read write"}`
+    const RS256_TOKEN: &str = "eyJhbGciOiJSUzI1NiIsInR5cCI6IkpXVCJ9.\
+        eyJleHAiOjQxMDI0NDQ4MDAsInNjb3BlIjoicmVhZCB3cml0ZSJ9.\
+        m-A3hkf7kfqrPTqKYG8ofrXZ_ZtghNW98nnTE54RRn6q7Z4hSLAhTJZZNhF_bPN29SleYyIfCVOggGzany8VaGYXQc\
+        xktImHaN-f6slzgd3oNfnQ9hd05GFEl0irznlPTtfkVON8orksS6CpQ0HOix4IUSH6fM9hi9zyo1ZkbwwQARJYf5cy5\
+        qCCO8mfskW1I4o5CsAuPqcttNzszbocVzm3JRwBp-Q4x7PLNESfYFVJz4t59QrOIqKGHsdmzI95-KJdvi7XFFvBcguW\
+        8uIwPchGiLOaTvsQ0PQCxPPR9AL44gwDVAuGZCbxHeRDL-fpFwJ9MMit-emgfr2Ol17FVA";
+
+    fn hs256_token(secret: &str, header: &str, claims: &str) -> String {
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(claims),
+        );
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let signature = hmac::sign(&key, signing_input.as_bytes());
+        format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    #[test]
+    fn test_verify_hs256() {
+        let verifier = JwtVerifier::new(&JwtConfig {
+            algorithm: JwtAlgorithm::Hs256,
+            key: "secret".into(),
+            audience: Some("qdrant".into()),
+            issuer: Some("issuer".into()),
+        })
+        .unwrap();
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let header = r#"{"alg":"HS256","typ":"JWT"}"#;
+        let verify = |token: &str| verifier.verify(token, now).map(|token| token.access);
+
+        let token = hs256_token(
+            "secret",
+            header,
+            r#"{"exp":1700000060,"aud":"qdrant","iss":"issuer","scope":"read admin other"}"#,
+        );
+        assert_eq!(verify(&token), Some(vec![Access::Read, Access::Admin]));
+
+        let token = hs256_token(
+            "secret",
+            header,
+            r#"{"exp":1700000060,"aud":"qdrant","iss":"issuer","sub":"client"}"#,
+        );
+        assert_eq!(
+            verifier.verify(&token, now),
+            Some(VerifiedToken {
+                access: vec![],
+                subject: Some("client".into()),
+            }),
+        );
+
+        let token = hs256_token(
+            "secret",
+            header,
+            r#"{"exp":1700000060,"aud":["other","qdrant"],"iss":"issuer"}"#,
+        );
+        assert_eq!(verify(&token), Some(vec![]));
+
+        // Expired, for another audience or issuer, or missing claims
+        let claims = [
+            r#"{"exp":1700000000,"aud":"qdrant","iss":"issuer","scope":"read"}"#,
+            r#"{"exp":1700000060,"aud":"other","iss":"issuer","scope":"read"}"#,
+            r#"{"exp":1700000060,"aud":"qdrant","iss":"other","scope":"read"}"#,
+            r#"{"exp":1700000060,"iss":"issuer","scope":"read"}"#,
+            r#"{"aud":"qdrant","iss":"issuer","scope":"read"}"#,
+        ];
+        for claims in claims {
+            assert_eq!(verify(&hs256_token("secret", header, claims)), None);
+        }
+
+        // Wrong secret, unexpected algorithm, tampered or malformed tokens
+        let claims = r#"{"exp":1700000060,"aud":"qdrant","iss":"issuer","scope":"read"}"#;
+        let token = hs256_token("secret", header, claims);
+        assert_eq!(verify(&hs256_token("other", header, claims)), None);
+        assert_eq!(
+            verify(&hs256_token("secret", r#"{"alg":"none"}"#, claims)),
+            None
+        );
+        assert_eq!(verify(&token.replacen('.', ".x", 1)), None);
+        assert_eq!(verify(&format!("{token}.")), None);
+        assert_eq!(verify("not a token"), None);
+    }
+
+    #[test]
+    fn test_verify_rs256() {
+        let verifier = JwtVerifier::new(&JwtConfig {
+            algorithm: JwtAlgorithm::Rs256,
+            key: RSA_PUBLIC_KEY.into(),
+            audience: None,
+            issuer: None,
+        })
+        .unwrap();
+        let now = SystemTime::now();
+
+        assert_eq!(
+            verifier.verify(RS256_TOKEN, now),
+            Some(VerifiedToken {
+                access: vec![Access::Read, Access::Write],
+                subject: None,
+            }),
+        );
+
+        // A HS256 token signed with the public key must not pass as RS256
+        let token = hs256_token(
+            RSA_PUBLIC_KEY,
+            r#"{"alg":"HS256"}"#,
+            r#"{"exp":4102444800,"scope":"admin"}"#,
+        );
+        assert_eq!(verifier.verify(&token, now), None);
+        assert_eq!(
+            verifier.verify(&RS256_TOKEN.replace("m-A3", "m-A4"), now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_jwt() {
+        assert!(is_jwt(RS256_TOKEN));
+        assert!(!is_jwt("my-secret-api-key"));
+        assert!(!is_jwt("a.b"));
+    }
+}
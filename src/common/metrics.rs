@@ -1,6 +1,7 @@
 use prometheus::proto::{Counter, Gauge, LabelPair, Metric, MetricFamily, MetricType};
 use prometheus::TextEncoder;
 
+use crate::common::auth::{AuthMetrics, AUTH_METRICS};
 use crate::common::telemetry::TelemetryData;
 use crate::common::telemetry_ops::app_telemetry::AppBuildTelemetry;
 use crate::common::telemetry_ops::cluster_telemetry::{ClusterStatusTelemetry, ClusterTelemetry};
@@ -59,6 +60,7 @@ impl From<TelemetryData> for MetricsData {
     fn from(telemetry_data: TelemetryData) -> Self {
         let mut metrics = vec![];
         telemetry_data.add_metrics(&mut metrics);
+        AUTH_METRICS.add_metrics(&mut metrics);
         Self { metrics }
     }
 }
@@ -331,6 +333,25 @@ impl MetricsProvider for GrpcTelemetry {
     }
 }
 
+impl MetricsProvider for AuthMetrics {
+    fn add_metrics(&self, metrics: &mut Vec<MetricFamily>) {
+        let requests = self
+            .counts()
+            .into_iter()
+            .map(|(result, key, count)| match key {
+                Some(key) => counter(count as f64, &[("result", result), ("key", key)]),
+                None => counter(count as f64, &[("result", result)]),
+            })
+            .collect();
+        metrics.push(metric_family(
+            "auth_requests_total",
+            "total number of requests checked for an api-key, by result",
+            MetricType::COUNTER,
+            requests,
+        ));
+    }
+}
+
 fn metric_family(name: &str, help: &str, r#type: MetricType, metrics: Vec<Metric>) -> MetricFamily {
     let mut metric_family = MetricFamily::default();
     metric_family.set_name(name.into());
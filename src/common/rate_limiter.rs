@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::settings::RateLimitConfig;
+
+/// Token bucket rate limiter, with a separate bucket for each key
+///
+/// Each bucket holds up to `capacity` tokens and is refilled continuously at `refill_per_second`.
+/// A request takes a single token, and is rejected if the bucket is empty.
+///
+/// Buckets that have refilled to capacity are evicted, since a new bucket starts full anyway.
+/// This keeps memory bounded by the number of recently active keys.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    capacity: f64,
+    refill_per_second: f64,
+    buckets: Mutex<Buckets<K>>,
+}
+
+#[derive(Debug)]
+struct Buckets<K> {
+    buckets: HashMap<K, Bucket>,
+    /// When full buckets were last evicted
+    last_eviction: Instant,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            capacity: config.capacity as f64,
+            refill_per_second: config.refill_per_second,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                last_eviction: Instant::now(),
+            }),
+        }
+    }
+
+    /// Try to take a token from the bucket of the given key.
+    ///
+    /// Returns `false` if the bucket is exhausted, in which case the request should be rejected.
+    pub fn try_acquire(&self, key: K) -> bool {
+        self.try_acquire_at(key, Instant::now())
+    }
+
+    fn try_acquire_at(&self, key: K, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock();
+
+        // Any bucket idle for the time it takes to refill an empty one is full, so checking that
+        // often is enough to evict all idle buckets
+        let refill_secs = self.capacity / self.refill_per_second;
+        let since_eviction = now.saturating_duration_since(buckets.last_eviction);
+        if since_eviction.as_secs_f64() >= refill_secs {
+            buckets.last_eviction = now;
+            buckets
+                .buckets
+                .retain(|_, bucket| self.refilled_tokens(bucket, now) < self.capacity);
+        }
+
+        let bucket = buckets.buckets.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            last_refill: now,
+        });
+
+        bucket.tokens = self.refilled_tokens(bucket, now);
+        bucket.last_refill = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Tokens in the bucket at `now`, after refilling it since it was last used
+    fn refilled_tokens(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        (bucket.tokens + elapsed.as_secs_f64() * self.refill_per_second).min(self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            capacity: 2,
+            refill_per_second: 1.0,
+        });
+        let start = Instant::now();
+
+        // Bucket starts full
+        assert!(limiter.try_acquire_at("a", start));
+        assert!(limiter.try_acquire_at("a", start));
+        assert!(!limiter.try_acquire_at("a", start));
+
+        // Buckets are separate per key
+        assert!(limiter.try_acquire_at("b", start));
+
+        // Refills over time, but never above capacity
+        assert!(!limiter.try_acquire_at("a", start + Duration::from_millis(500)));
+        assert!(limiter.try_acquire_at("a", start + Duration::from_millis(1000)));
+        assert!(!limiter.try_acquire_at("a", start + Duration::from_millis(1000)));
+        let later = start + Duration::from_secs(60);
+        assert!(limiter.try_acquire_at("a", later));
+        assert!(limiter.try_acquire_at("a", later));
+        assert!(!limiter.try_acquire_at("a", later));
+    }
+
+    #[test]
+    fn test_rate_limiter_evicts_full_buckets() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            capacity: 2,
+            refill_per_second: 1.0,
+        });
+        let start = Instant::now();
+        let len = || limiter.buckets.lock().buckets.len();
+
+        for key in 0..100 {
+            assert!(limiter.try_acquire_at(key, start));
+        }
+        let exhausted = start + Duration::from_millis(1900);
+        assert!(limiter.try_acquire_at(0, exhausted));
+        assert!(limiter.try_acquire_at(0, exhausted));
+        assert!(!limiter.try_acquire_at(0, exhausted));
+        assert_eq!(len(), 100);
+
+        // Idle buckets refilled to capacity and are evicted, the exhausted one is kept
+        let later = start + Duration::from_secs(2);
+        assert!(limiter.try_acquire_at(100, later));
+        assert_eq!(len(), 2);
+        assert!(!limiter.try_acquire_at(0, later));
+    }
+}
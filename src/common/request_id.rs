@@ -0,0 +1,77 @@
+use std::fmt;
+
+use uuid::Uuid;
+
+/// Header carrying the id of a request, to correlate the logs of proxies and Qdrant
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request id accepted from a client, longer ids are replaced by a generated one
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Id of a request, inserted into the request extensions by the api-key middlewares
+///
+/// Taken from the `X-Request-Id` header of the request if present, otherwise a random UUID is
+/// generated. The id is sent back in the same header of the response, and is part of the logged
+/// outcome of the api-key check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Id given by the client in the `X-Request-Id` header, or a new random id if there is none.
+    ///
+    /// Ids that are empty, too long or contain anything but printable ASCII are replaced as well,
+    /// so clients can't forge log lines and the id is always a valid header value.
+    pub fn from_header(value: Option<&str>) -> Self {
+        match value {
+            Some(id) if is_valid(id) => Self(id.to_string()),
+            _ => Self::generate(),
+        }
+    }
+
+    /// New random id
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LENGTH && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id_from_header() {
+        assert_eq!(
+            RequestId::from_header(Some("proxy-1234")).as_str(),
+            "proxy-1234",
+        );
+
+        // Missing or malformed ids are replaced with a random UUID
+        let too_long = "a".repeat(MAX_REQUEST_ID_LENGTH + 1);
+        for value in [
+            None,
+            Some(""),
+            Some("with space"),
+            Some("line\nbreak"),
+            Some(too_long.as_str()),
+        ] {
+            let id = RequestId::from_header(value);
+            assert!(Uuid::parse_str(id.as_str()).is_ok(), "{value:?} gave {id}");
+        }
+
+        assert_ne!(RequestId::generate(), RequestId::generate());
+    }
+}
@@ -32,10 +32,12 @@ use storage::dispatcher::Dispatcher;
 #[cfg(not(target_env = "msvc"))]
 use tikv_jemallocator::Jemalloc;
 
+use crate::common::auth::{AuthKeys, AuthKeysHandle, KeyFile};
 use crate::common::helpers::{
     create_general_purpose_runtime, create_search_runtime, create_update_runtime,
     load_tls_client_config,
 };
+use crate::common::rate_limiter::RateLimiter;
 use crate::common::telemetry::TelemetryCollector;
 use crate::common::telemetry_reporting::TelemetryReporter;
 use crate::greeting::welcome;
@@ -109,7 +111,7 @@ fn main() -> anyhow::Result<()> {
     remove_started_file_indicator();
 
     let args = Args::parse();
-    let settings = Settings::new(args.config_path)?;
+    let settings = Settings::new(args.config_path.clone())?;
 
     let reporting_enabled = !settings.telemetry_disabled && !args.disable_telemetry;
 
@@ -118,7 +120,7 @@ fn main() -> anyhow::Result<()> {
     setup_logger(&settings.log_level);
     setup_panic_hook(reporting_enabled, reporting_id.to_string());
 
-    segment::madvise::set_global(settings.storage.mmap_advice);
+    segment::madvise::set_global(settings.storage.mmap_advice)?;
 
     welcome();
 
@@ -336,6 +338,44 @@ fn main() -> anyhow::Result<()> {
         log::info!("Telemetry reporting disabled");
     }
 
+    //
+    // Api-keys
+    //
+
+    let auth_keys = match AuthKeys::from_config(&settings.service)? {
+        Some(auth_keys) => Some(AuthKeysHandle::new(auth_keys)),
+        // Without any keys, no request can authenticate and all of them are rejected
+        None if settings.service.require_auth => {
+            log::warn!(
+                "Authentication is required, but no api-key is configured. \
+                 All requests are rejected, except for unauthenticated paths"
+            );
+            Some(AuthKeysHandle::default())
+        }
+        None => None,
+    };
+
+    if let (Some(auth_keys), Some(path)) = (&auth_keys, &settings.service.api_key_file) {
+        // Load the keys before serving requests, then watch for changes
+        let mut key_file = KeyFile::new(path, settings.service.api_key_hashed);
+        key_file.reload_if_changed(auth_keys);
+        runtime_handle.spawn(common::auth::watch_key_file(auth_keys.clone(), key_file));
+    }
+
+    // Shared by the REST and gRPC servers, so each client has a single quota across both
+    let rate_limiter = settings
+        .service
+        .api_key_rate_limit
+        .as_ref()
+        .map(|config| Arc::new(RateLimiter::new(config)));
+
+    #[cfg(unix)]
+    {
+        if let Some(auth_keys) = auth_keys.clone() {
+            runtime_handle.spawn(common::auth::reload_on_sighup(auth_keys, args.config_path));
+        }
+    }
+
     //
     // REST API server
     //
@@ -344,9 +384,19 @@ fn main() -> anyhow::Result<()> {
     {
         let dispatcher_arc = dispatcher_arc.clone();
         let settings = settings.clone();
+        let auth_keys = auth_keys.clone();
+        let rate_limiter = rate_limiter.clone();
         let handle = thread::Builder::new()
             .name("web".to_string())
-            .spawn(move || actix::init(dispatcher_arc.clone(), telemetry_collector, settings))
+            .spawn(move || {
+                actix::init(
+                    dispatcher_arc.clone(),
+                    telemetry_collector,
+                    settings,
+                    auth_keys,
+                    rate_limiter,
+                )
+            })
             .unwrap();
         handles.push(handle);
     }
@@ -366,6 +416,8 @@ fn main() -> anyhow::Result<()> {
                     settings,
                     grpc_port,
                     runtime_handle,
+                    auth_keys,
+                    rate_limiter,
                 )
             })
             .unwrap();
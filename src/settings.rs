@@ -3,6 +3,7 @@ use std::{env, io};
 use api::grpc::transport_channel_pool::{
     DEFAULT_CONNECT_TIMEOUT, DEFAULT_GRPC_TIMEOUT, DEFAULT_POOL_SIZE,
 };
+use chrono::{DateTime, Utc};
 use collection::operations::validation;
 use config::{Config, ConfigError, Environment, File, FileFormat, Source};
 use segment::common::cpu::get_num_cpus;
@@ -10,6 +11,8 @@ use serde::Deserialize;
 use storage::types::StorageConfig;
 use validator::Validate;
 
+use crate::common::auth::Access;
+
 const DEFAULT_CONFIG: &str = include_str!("../config/config.yaml");
 
 #[derive(Debug, Deserialize, Validate, Clone)]
@@ -26,7 +29,212 @@ pub struct ServiceConfig {
     pub enable_tls: bool,
     #[serde(default)]
     pub verify_https_client_certificate: bool,
+    /// Reject all requests if no api-key is configured, instead of not requiring authentication
+    #[serde(default)]
+    pub require_auth: bool,
     pub api_key: Option<String>,
+    /// Additional api-keys, each of them is accepted just like `api_key`
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// File with additional api-keys, one per line, reloaded whenever it changes
+    #[serde(default)]
+    pub api_key_file: Option<String>,
+    /// Api-key granting access to read-only operations only
+    pub read_only_api_key: Option<String>,
+    /// Additional read-only api-keys, each of them is accepted just like `read_only_api_key`
+    #[serde(default)]
+    pub read_only_api_keys: Vec<ApiKeyConfig>,
+    /// Api-keys granting access to the configured operation classes only
+    #[serde(default)]
+    #[validate]
+    pub scoped_api_keys: Vec<ScopedApiKeyConfig>,
+    /// Name of the header carrying the api-key
+    #[serde(default = "default_api_key_header")]
+    pub api_key_header: String,
+    /// Accept the api-key from the `api_key` query parameter if the header is missing (REST only)
+    #[serde(default)]
+    pub api_key_in_query: bool,
+    /// Accept the api-key as bearer token in the `Authorization` header
+    #[serde(default)]
+    pub api_key_bearer: bool,
+    /// Accept the api-key as password of Basic auth in the `Authorization` header
+    #[serde(default)]
+    pub api_key_basic_auth: bool,
+    /// Longest api-key accepted, in bytes. Longer values are rejected without comparing them.
+    #[serde(default = "default_api_key_max_length")]
+    #[validate(range(min = 1))]
+    pub api_key_max_length: usize,
+    /// Per api-key rate limit, disabled if not set
+    #[serde(default)]
+    #[validate]
+    pub api_key_rate_limit: Option<RateLimitConfig>,
+    /// Accept requests signed with a shared secret, as an alternative to api-keys
+    #[serde(default)]
+    #[validate]
+    pub request_signing: Option<RequestSigningConfig>,
+    /// Accept JWT bearer tokens, as an alternative to api-keys
+    #[serde(default)]
+    #[validate]
+    pub jwt: Option<JwtConfig>,
+    /// Networks allowed to make write and admin requests, unrestricted if not set
+    #[serde(default)]
+    pub write_ip_allowlist: Option<IpAllowlistConfig>,
+    /// Request paths that skip the api-key check, both for REST and gRPC. None by default, paths
+    /// must be opted in explicitly.
+    #[serde(default)]
+    pub unauthenticated_paths: Vec<UnauthenticatedPathConfig>,
+    /// Let OPTIONS requests, such as CORS preflight requests, skip the api-key check (REST only)
+    #[serde(default = "default_allow_unauthenticated_options")]
+    pub allow_unauthenticated_options: bool,
+    /// Let gRPC services in `grpc_unauthenticated_services` skip the api-key check
+    #[serde(default)]
+    pub allow_unauthenticated_grpc_services: bool,
+    /// Prefixes of fully qualified gRPC service names, such as `grpc.health.`
+    #[serde(default = "default_grpc_unauthenticated_services")]
+    pub grpc_unauthenticated_services: Vec<String>,
+    /// gRPC methods read-only api-keys may never call, as `{package}.{Service}/{Method}`
+    #[serde(default = "default_grpc_read_only_denied_methods")]
+    pub grpc_read_only_denied_methods: Vec<String>,
+    /// HTTP methods the read-only api-key may be used for on any REST route
+    #[serde(default = "default_read_only_methods")]
+    pub read_only_methods: Vec<String>,
+    /// Other REST routes the read-only api-key may be used for
+    #[serde(default = "default_read_only_routes")]
+    pub read_only_routes: Vec<ReadOnlyRouteConfig>,
+    /// Configured api-keys are hex encoded SHA-256 digests rather than plaintext keys
+    #[serde(default)]
+    pub api_key_hashed: bool,
+    /// HTTP status code of responses to requests that are not permitted
+    #[serde(default = "default_api_key_rejection_status")]
+    #[validate(range(min = 400, max = 499))]
+    pub api_key_rejection_status: u16,
+}
+
+/// Api-key, optionally with a label and expiring at the given time
+///
+/// The label identifies the key in logs and is passed on to request handlers.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ApiKeyConfig {
+    Key(String),
+    Detailed {
+        key: String,
+        #[serde(default)]
+        label: Option<String>,
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
+    },
+}
+
+impl ApiKeyConfig {
+    pub fn key(&self) -> &str {
+        match self {
+            ApiKeyConfig::Key(key) => key,
+            ApiKeyConfig::Detailed { key, .. } => key,
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            ApiKeyConfig::Key(_) => None,
+            ApiKeyConfig::Detailed { label, .. } => label.as_deref(),
+        }
+    }
+
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            ApiKeyConfig::Key(_) => None,
+            ApiKeyConfig::Detailed { expires_at, .. } => *expires_at,
+        }
+    }
+}
+
+/// Api-key limited to the given operation classes, identified by its label in logs
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct ScopedApiKeyConfig {
+    pub key: String,
+    #[validate(length(min = 1))]
+    pub label: String,
+    #[validate(length(min = 1))]
+    pub permissions: Vec<Access>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ScopedApiKeyConfig {
+    pub fn api_key(&self) -> ApiKeyConfig {
+        ApiKeyConfig::Detailed {
+            key: self.key.clone(),
+            label: Some(self.label.clone()),
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// Token bucket rate limit
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct RateLimitConfig {
+    /// Maximum number of requests in a burst
+    #[validate(range(min = 1))]
+    pub capacity: u32,
+    /// Number of requests the bucket is refilled with per second
+    #[validate(range(min = 0.0))]
+    pub refill_per_second: f64,
+}
+
+/// Shared secret for verifying signed requests
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct RequestSigningConfig {
+    #[validate(length(min = 1))]
+    pub secret: String,
+    /// Maximum difference between the request timestamp and the server time
+    #[serde(default = "default_max_clock_skew_sec")]
+    pub max_clock_skew_sec: u64,
+}
+
+/// Key and expected claims for verifying JWT bearer tokens
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct JwtConfig {
+    pub algorithm: JwtAlgorithm,
+    /// Shared secret for HS256, or PEM encoded PKCS#1 RSA public key for RS256
+    #[validate(length(min = 1))]
+    pub key: String,
+    /// Expected `aud` claim, not checked if not set
+    pub audience: Option<String>,
+    /// Expected `iss` claim, not checked if not set
+    pub issuer: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    #[serde(rename = "HS256")]
+    Hs256,
+    #[serde(rename = "RS256")]
+    Rs256,
+}
+
+/// Networks that client addresses must belong to
+#[derive(Debug, Deserialize, Clone)]
+pub struct IpAllowlistConfig {
+    /// Networks in CIDR notation, or single addresses
+    pub networks: Vec<String>,
+    /// Take the client address from the `X-Forwarded-For` header set by a trusted proxy
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+}
+
+/// Request path, matched either exactly or as a prefix
+#[derive(Debug, Deserialize, Clone)]
+pub struct UnauthenticatedPathConfig {
+    pub path: String,
+    #[serde(default)]
+    pub prefix: bool,
+}
+
+/// REST route, given as HTTP method and path pattern
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReadOnlyRouteConfig {
+    pub method: String,
+    pub path: String,
 }
 
 #[derive(Debug, Deserialize, Clone, Default, Validate)]
@@ -173,6 +381,75 @@ fn default_cors() -> bool {
     true
 }
 
+fn default_allow_unauthenticated_options() -> bool {
+    true
+}
+
+fn default_api_key_header() -> String {
+    "api-key".to_string()
+}
+
+fn default_api_key_max_length() -> usize {
+    1024
+}
+
+fn default_api_key_rejection_status() -> u16 {
+    403
+}
+
+/// Standard gRPC server reflection and health checking services
+fn default_grpc_unauthenticated_services() -> Vec<String> {
+    vec!["grpc.reflection.".to_string(), "grpc.health.".to_string()]
+}
+
+/// Mutating methods of the Points and Collections services
+fn default_grpc_read_only_denied_methods() -> Vec<String> {
+    [
+        "qdrant.Collections/Create",
+        "qdrant.Collections/Delete",
+        "qdrant.Collections/Update",
+        "qdrant.Collections/UpdateAliases",
+        "qdrant.Collections/UpdateCollectionClusterSetup",
+        "qdrant.Points/ClearPayload",
+        "qdrant.Points/CreateFieldIndex",
+        "qdrant.Points/Delete",
+        "qdrant.Points/DeleteFieldIndex",
+        "qdrant.Points/DeletePayload",
+        "qdrant.Points/DeleteVectors",
+        "qdrant.Points/OverwritePayload",
+        "qdrant.Points/SetPayload",
+        "qdrant.Points/UpdateVectors",
+        "qdrant.Points/Upsert",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
+fn default_max_clock_skew_sec() -> u64 {
+    300
+}
+
+pub fn default_read_only_methods() -> Vec<String> {
+    vec!["GET".to_string()]
+}
+
+/// Read operations in the REST API that use POST
+pub fn default_read_only_routes() -> Vec<ReadOnlyRouteConfig> {
+    [
+        "/collections/{name}/points/search",
+        "/collections/{name}/points/recommend",
+        "/collections/{name}/points/scroll",
+        "/collections/{name}/points/count",
+    ]
+    .into_iter()
+    .map(|path| ReadOnlyRouteConfig {
+        method: "POST".to_string(),
+        path: path.to_string(),
+    })
+    .collect()
+}
+
 fn default_debug() -> bool {
     false
 }
@@ -266,6 +543,24 @@ impl Settings {
         // Build and merge config and deserialize into Settings, attach any load errors we had
         let mut settings: Settings = config.build()?.try_deserialize()?;
         settings.load_errors.extend(load_errors);
+
+        // Validation errors are only logged, but any other status would report rejected requests
+        // to clients as successful or redirected
+        let rejection_status = settings.service.api_key_rejection_status;
+        if !(400..=499).contains(&rejection_status) {
+            return Err(ConfigError::Message(format!(
+                "service.api_key_rejection_status must be a 4xx status code, got {rejection_status}"
+            )));
+        }
+
+        // Applied to all memory maps, which must never lose data
+        let mmap_advice = settings.storage.mmap_advice;
+        if mmap_advice.discards_data() {
+            return Err(ConfigError::Message(format!(
+                "storage.mmap_advice must not discard data, got {mmap_advice}"
+            )));
+        }
+
         Ok(settings)
     }
 }
@@ -307,6 +602,18 @@ mod tests {
             .expect("failed to validate default config");
     }
 
+    /// No request may skip authentication unless configured explicitly.
+    #[test]
+    fn test_default_unauthenticated_paths() {
+        let settings = Config::builder()
+            .add_source(File::from_str(DEFAULT_CONFIG, FileFormat::Yaml))
+            .build()
+            .expect("failed to build default config")
+            .try_deserialize::<Settings>()
+            .expect("failed to deserialize default config");
+        assert!(settings.service.unauthenticated_paths.is_empty());
+    }
+
     #[sealed_test(files = ["config/config.yaml", "config/development.yaml"])]
     fn test_runtime_development_config() {
         env::set_var("RUN_MODE", "development");
@@ -361,4 +668,36 @@ mod tests {
         // Ensure our custom config is the most important
         assert_eq!(config.service.http_port, 9999);
     }
+
+    #[sealed_test]
+    fn test_invalid_rejection_status() {
+        let path = "config/custom.yaml";
+
+        {
+            fs::create_dir("config").unwrap();
+            let mut custom = fs::File::create(path).unwrap();
+            write!(&mut custom, "service:\n    api_key_rejection_status: 200").unwrap();
+            custom.flush().unwrap();
+        }
+
+        let err = Settings::new(Some(path.into())).unwrap_err();
+        assert!(
+            err.to_string().contains("api_key_rejection_status"),
+            "{err}"
+        );
+    }
+
+    #[sealed_test]
+    fn test_discarding_mmap_advice() {
+        fs::create_dir("config").unwrap();
+        for advice in ["dont_need", "free"] {
+            let path = format!("config/{advice}.yaml");
+            let mut custom = fs::File::create(&path).unwrap();
+            write!(&mut custom, "storage:\n    mmap_advice: {advice}").unwrap();
+            custom.flush().unwrap();
+
+            let err = Settings::new(Some(path)).unwrap_err();
+            assert!(err.to_string().contains("mmap_advice"), "{err}");
+        }
+    }
 }
@@ -1,31 +1,210 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use constant_time_eq::constant_time_eq;
 use futures_util::future::BoxFuture;
-use reqwest::header::HeaderValue;
+use reqwest::header;
 use reqwest::StatusCode;
 use tonic::body::BoxBody;
-use tonic::Code;
+use tonic::codegen::Body as _;
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tonic::{Code, Status};
 use tower::Service;
 use tower_layer::Layer;
 
+use crate::common::auth::{
+    auth_trace, Access, ApiKeySources, AuthError, AuthKeys, AuthKeysHandle, AuthLogContext,
+    AuthOutcome, KeyId, RateLimitKey, UnauthenticatedPaths, AUTH_METRICS,
+};
+use crate::common::ip_allowlist::IpAllowlist;
+use crate::common::rate_limiter::RateLimiter;
+use crate::common::request_id::{RequestId, REQUEST_ID_HEADER};
+use crate::common::request_signing::{SignedRequest, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+/// gRPC methods that only read data, and may therefore be called with a read-only api-key.
+///
+/// This array *must* be sorted.
+const READ_ONLY_GRPC_METHODS: &[&str] = &[
+    "/qdrant.Collections/CollectionClusterInfo",
+    "/qdrant.Collections/Get",
+    "/qdrant.Collections/List",
+    "/qdrant.Collections/ListAliases",
+    "/qdrant.Collections/ListCollectionAliases",
+    "/qdrant.Points/Count",
+    "/qdrant.Points/Get",
+    "/qdrant.Points/Recommend",
+    "/qdrant.Points/RecommendBatch",
+    "/qdrant.Points/RecommendGroups",
+    "/qdrant.Points/Scroll",
+    "/qdrant.Points/Search",
+    "/qdrant.Points/SearchBatch",
+    "/qdrant.Points/SearchGroups",
+    "/qdrant.Qdrant/HealthCheck",
+    "/qdrant.Snapshots/List",
+    "/qdrant.Snapshots/ListFull",
+];
+
+/// gRPC methods that manage collections, aliases, snapshots or the cluster.
+///
+/// This array *must* be sorted.
+const ADMIN_GRPC_METHODS: &[&str] = &[
+    "/qdrant.Collections/Create",
+    "/qdrant.Collections/Delete",
+    "/qdrant.Collections/Update",
+    "/qdrant.Collections/UpdateAliases",
+    "/qdrant.Collections/UpdateCollectionClusterSetup",
+    "/qdrant.Snapshots/Create",
+    "/qdrant.Snapshots/CreateFull",
+    "/qdrant.Snapshots/Delete",
+    "/qdrant.Snapshots/DeleteFull",
+];
+
+/// Classify a gRPC request by its URI path, which has the form `/{package}.{Service}/{Method}`.
+///
+/// gRPC requests are always POST, so unlike REST the HTTP method tells nothing about access.
+/// Unknown methods are considered to require write access, and so are the read methods in
+/// `read_only_denied`, given as `{package}.{Service}/{Method}`.
+fn grpc_method_access(path: &str, read_only_denied: &HashSet<String>) -> Access {
+    let is_denied =
+        matches!(path.strip_prefix('/'), Some(method) if read_only_denied.contains(method));
+    if READ_ONLY_GRPC_METHODS.binary_search(&path).is_ok() && !is_denied {
+        Access::Read
+    } else if ADMIN_GRPC_METHODS.binary_search(&path).is_ok() {
+        Access::Admin
+    } else {
+        Access::Write
+    }
+}
+
 #[derive(Clone)]
 pub struct ApiKeyMiddleware<T> {
     service: T,
-    api_key: String,
+    auth_keys: AuthKeysHandle,
+    key_sources: Arc<ApiKeySources>,
+    unauthenticated_paths: Arc<UnauthenticatedPaths>,
+    rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
+    ip_allowlist: Option<Arc<IpAllowlist>>,
+    rejection_status: StatusCode,
+    /// Read methods that read-only keys may not call nonetheless
+    read_only_denied_methods: Arc<HashSet<String>>,
 }
 
 #[derive(Clone)]
 pub struct ApiKeyMiddlewareLayer {
-    api_key: String,
+    auth_keys: AuthKeysHandle,
+    key_sources: Arc<ApiKeySources>,
+    unauthenticated_paths: Arc<UnauthenticatedPaths>,
+    rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
+    ip_allowlist: Option<Arc<IpAllowlist>>,
+    rejection_status: StatusCode,
+    read_only_denied_methods: Arc<HashSet<String>>,
+}
+
+impl<T> ApiKeyMiddleware<T> {
+    /// Check the api-key and rate limit of the request.
+    ///
+    /// `key` is the api-key of the request, see [`ApiKeyMiddleware::request_key`].
+    fn authenticate(
+        &self,
+        request: &tonic::codegen::http::Request<tonic::transport::Body>,
+        key: Option<Result<&str, AuthError>>,
+        access: Access,
+        auth_keys: &AuthKeys,
+    ) -> AuthOutcome {
+        // JWT bearer tokens are tried first, anything else is checked as api-key
+        let authorization = request.headers().get(header::AUTHORIZATION);
+        if let Some(token) = auth_keys.jwt_bearer_token(authorization) {
+            auth_trace!("gRPC {}: checking jwt bearer token", request.uri().path());
+            return match auth_keys.verify_jwt(token, access) {
+                Ok((key_id, rate_limit_key)) => {
+                    AuthOutcome::authenticated(key_id, rate_limit_key, self.rate_limiter.as_deref())
+                }
+                Err(err) => AuthOutcome::from(err),
+            };
+        }
+
+        auth_trace!("gRPC {}: checking api-key", request.uri().path());
+        // `None` if the request has no credentials
+        let authenticated = key.map(|key| {
+            key.and_then(|key| {
+                let key_id = auth_keys.authenticate(key, access)?;
+                Ok((key_id, RateLimitKey::digest(key)))
+            })
+        });
+
+        match authenticated {
+            None => {
+                auth_keys.compare_missing_key();
+                AuthOutcome::Unauthorized
+            }
+            Some(Err(err)) => AuthOutcome::from(err),
+            Some(Ok((key_id, rate_limit_key))) => {
+                AuthOutcome::authenticated(key_id, rate_limit_key, self.rate_limiter.as_deref())
+            }
+        }
+    }
+
+    /// Api-key from the configured header.
+    ///
+    /// If enabled, a bearer token or Basic auth password in the `Authorization` header takes
+    /// precedence.
+    fn request_key<'a>(
+        &self,
+        request: &'a tonic::codegen::http::Request<tonic::transport::Body>,
+    ) -> Option<Result<Cow<'a, str>, AuthError>> {
+        let authorization = request.headers().get(header::AUTHORIZATION);
+        if let Some(key) = self.key_sources.authorization_key(authorization) {
+            return Some(key);
+        }
+
+        request
+            .headers()
+            .get(self.key_sources.header_name.as_str())
+            .map(|key| self.key_sources.header_key(key).map(Cow::Borrowed))
+    }
+
+    /// Check the client address against the ip allowlist, if configured.
+    fn address_allowed(
+        &self,
+        request: &tonic::codegen::http::Request<tonic::transport::Body>,
+    ) -> bool {
+        match &self.ip_allowlist {
+            Some(ip_allowlist) => {
+                let forwarded_for = request
+                    .headers()
+                    .get_all(header::X_FORWARDED_FOR)
+                    .iter()
+                    .last()
+                    .and_then(|value| value.to_str().ok());
+                ip_allowlist.allows(peer_addr(request), forwarded_for)
+            }
+            None => true,
+        }
+    }
+}
+
+/// Peer address of the connection, set by the tonic server in the request extensions
+fn peer_addr(request: &tonic::codegen::http::Request<tonic::transport::Body>) -> Option<IpAddr> {
+    let extensions = request.extensions();
+    let remote_addr = match extensions.get::<TcpConnectInfo>() {
+        Some(connect_info) => connect_info.remote_addr(),
+        None => extensions
+            .get::<TlsConnectInfo<TcpConnectInfo>>()
+            .and_then(|connect_info| connect_info.get_ref().remote_addr()),
+    };
+    remote_addr.map(|addr| addr.ip())
 }
 
 impl<S> Service<tonic::codegen::http::Request<tonic::transport::Body>> for ApiKeyMiddleware<S>
 where
     S: Service<
-        tonic::codegen::http::Request<tonic::transport::Body>,
-        Response = tonic::codegen::http::Response<tonic::body::BoxBody>,
-    >,
+            tonic::codegen::http::Request<tonic::transport::Body>,
+            Response = tonic::codegen::http::Response<tonic::body::BoxBody>,
+        > + Clone
+        + Send
+        + 'static,
     S::Future: Send + 'static,
 {
     type Response = tonic::codegen::http::Response<tonic::body::BoxBody>;
@@ -38,38 +217,281 @@ where
 
     fn call(
         &mut self,
-        request: tonic::codegen::http::Request<tonic::transport::Body>,
+        mut request: tonic::codegen::http::Request<tonic::transport::Body>,
     ) -> Self::Future {
-        if let Some(key) = request.headers().get("api-key") {
-            if let Ok(key) = key.to_str() {
-                if constant_time_eq(self.api_key.as_bytes(), key.as_bytes()) {
-                    let future = self.service.call(request);
-
-                    return Box::pin(async move {
-                        let response = future.await?;
-                        Ok(response)
-                    });
-                }
+        let request_id = RequestId::from_header(
+            request
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        );
+        request.extensions_mut().insert(request_id.clone());
+
+        let future = self.authenticate_and_call(request, request_id.clone());
+        Box::pin(async move {
+            let mut response = future.await?;
+            if let Ok(value) = header::HeaderValue::from_str(request_id.as_str()) {
+                response
+                    .headers_mut()
+                    .insert(header::HeaderName::from_static(REQUEST_ID_HEADER), value);
             }
+            Ok(response)
+        })
+    }
+}
+
+impl<S> ApiKeyMiddleware<S>
+where
+    S: Service<
+            tonic::codegen::http::Request<tonic::transport::Body>,
+            Response = tonic::codegen::http::Response<tonic::body::BoxBody>,
+        > + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    /// Check the api-key of the request, and call the wrapped service if it is permitted.
+    fn authenticate_and_call(
+        &mut self,
+        mut request: tonic::codegen::http::Request<tonic::transport::Body>,
+        request_id: RequestId,
+    ) -> BoxFuture<'static, Result<tonic::codegen::http::Response<BoxBody>, S::Error>> {
+        if self.unauthenticated_paths.contains(request.uri().path()) {
+            auth_trace!("gRPC {}: skipped, unauthenticated", request.uri().path());
+            return Box::pin(self.service.call(request));
         }
 
-        let mut response = Self::Response::new(BoxBody::default());
-        *response.status_mut() = StatusCode::FORBIDDEN;
-        response.headers_mut().append(
-            "grpc-status",
-            HeaderValue::from(Code::PermissionDenied as i32),
+        let access = grpc_method_access(request.uri().path(), &self.read_only_denied_methods);
+        let auth_keys = self.auth_keys.load_full();
+        let is_signed = request.headers().contains_key(SIGNATURE_HEADER)
+            && auth_keys.request_signer().is_some();
+        // Parsed once, for both checking and logging the api-key
+        let request_key = self.request_key(&request);
+        let key = request_key
+            .as_ref()
+            .map(|key| key.as_deref().map_err(|&err| err));
+
+        // Checked before the api-key, so disallowed addresses don't get to probe for keys
+        let outcome = if access != Access::Read && !self.address_allowed(&request) {
+            auth_trace!("gRPC {}: address not allowed", request.uri().path());
+            AuthOutcome::AddressNotAllowed
+        } else if is_signed {
+            auth_trace!("gRPC {}: checking signature", request.uri().path());
+            // The body must be read before calling the service, so take the service that is ready
+            // and leave a fresh clone in its place
+            let clone = self.service.clone();
+            let mut service = std::mem::replace(&mut self.service, clone);
+            let rate_limiter = self.rate_limiter.clone();
+            let rejection_status = self.rejection_status;
+            return Box::pin(async move {
+                let (mut request, verified) = match authenticate_signed(request, &auth_keys).await {
+                    Ok(authenticated) => authenticated,
+                    Err(response) => return Ok(response),
+                };
+                let outcome = match verified {
+                    Ok((key_id, rate_limit_key)) => {
+                        AuthOutcome::authenticated(key_id, rate_limit_key, rate_limiter.as_deref())
+                    }
+                    Err(err) => AuthOutcome::from(err),
+                };
+                AUTH_METRICS.record(outcome);
+                log_outcome(&request, None, &request_id, outcome, &auth_keys);
+
+                match rejection_response(outcome, rejection_status) {
+                    Some(response) => Ok(response),
+                    None => {
+                        insert_auth_context(&mut request, outcome, &auth_keys);
+                        service.call(request).await
+                    }
+                }
+            });
+        } else {
+            self.authenticate(&request, key, access, &auth_keys)
+        };
+        AUTH_METRICS.record(outcome);
+        log_outcome(
+            &request,
+            key.and_then(Result::ok),
+            &request_id,
+            outcome,
+            &auth_keys,
         );
-        response
-            .headers_mut()
-            .append("grpc-message", HeaderValue::from_static("Invalid api-key"));
 
-        Box::pin(async move { Ok(response) })
+        match rejection_response(outcome, self.rejection_status) {
+            Some(response) => Box::pin(async move { Ok(response) }),
+            None => {
+                insert_auth_context(&mut request, outcome, &auth_keys);
+                let future = self.service.call(request);
+                Box::pin(async move {
+                    let response = future.await?;
+                    Ok(response)
+                })
+            }
+        }
     }
 }
 
+type SignedRequestResult = (
+    tonic::codegen::http::Request<tonic::transport::Body>,
+    Result<(KeyId, RateLimitKey), AuthError>,
+);
+
+/// Verify the signature of a signed request.
+///
+/// The body is covered by the signature, so it is buffered and put back for the wrapped service.
+/// Fails with an error response if the body can't be read.
+async fn authenticate_signed(
+    request: tonic::codegen::http::Request<tonic::transport::Body>,
+    auth_keys: &AuthKeys,
+) -> Result<SignedRequestResult, tonic::codegen::http::Response<BoxBody>> {
+    let max_body_size = match auth_keys.request_signer() {
+        Some(request_signer) => request_signer.max_body_size(),
+        None => return Ok((request, Err(AuthError::Invalid))),
+    };
+
+    let (parts, mut body) = request.into_parts();
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| {
+            grpc_error_response(Code::InvalidArgument, "Failed to read request body")
+        })?;
+        if buffer.len() + chunk.len() > max_body_size {
+            return Err(grpc_error_response(
+                Code::ResourceExhausted,
+                "Request body too large",
+            ));
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let path = match parts.uri.path_and_query() {
+        Some(path_and_query) => path_and_query.as_str(),
+        None => parts.uri.path(),
+    };
+    let signed_request = SignedRequest {
+        method: parts.method.as_str(),
+        path,
+        timestamp: header(TIMESTAMP_HEADER),
+        signature: header(SIGNATURE_HEADER),
+        body: &buffer,
+    };
+    let verified = auth_keys.verify_signature(&signed_request);
+
+    let request = tonic::codegen::http::Request::from_parts(parts, buffer.into());
+    Ok((request, verified))
+}
+
+/// Log the outcome of the api-key check of the request, with the api-key it was sent with.
+fn log_outcome(
+    request: &tonic::codegen::http::Request<tonic::transport::Body>,
+    key: Option<&str>,
+    request_id: &RequestId,
+    outcome: AuthOutcome,
+    auth_keys: &AuthKeys,
+) {
+    let context = AuthLogContext {
+        method: request.method().as_str(),
+        path: request.uri().path(),
+        peer: peer_addr(request),
+        key,
+        request_id,
+    };
+    context.log(outcome, auth_keys);
+}
+
+/// Make the key the request was authenticated with available to the gRPC services, as
+/// `AuthContext`.
+fn insert_auth_context(
+    request: &mut tonic::codegen::http::Request<tonic::transport::Body>,
+    outcome: AuthOutcome,
+    auth_keys: &AuthKeys,
+) {
+    if let AuthOutcome::Ok(key_id) = outcome {
+        request
+            .extensions_mut()
+            .insert(auth_keys.auth_context(key_id));
+    }
+}
+
+/// Response rejecting the request, or `None` if the request may pass
+///
+/// Requests that are not permitted are rejected with the configured `rejection_status`.
+fn rejection_response(
+    outcome: AuthOutcome,
+    rejection_status: StatusCode,
+) -> Option<tonic::codegen::http::Response<BoxBody>> {
+    let response = match outcome {
+        AuthOutcome::Ok(_) => return None,
+        // Expired keys get the same response as invalid ones, only logs and metrics differ
+        AuthOutcome::Unauthorized | AuthOutcome::Forbidden | AuthOutcome::Expired => {
+            forbidden_grpc_response(rejection_status, "Invalid api-key")
+        }
+        AuthOutcome::RateLimited => {
+            grpc_error_response(Code::ResourceExhausted, "Too many requests for api-key")
+        }
+        AuthOutcome::AddressNotAllowed => {
+            forbidden_grpc_response(rejection_status, "Address not allowed to modify data")
+        }
+        AuthOutcome::InvalidToken => grpc_error_response(Code::Unauthenticated, "Invalid token"),
+    };
+    Some(response)
+}
+
+/// Response rejecting a gRPC request as not permitted
+///
+/// The gRPC status matches the configured rejection status.
+fn forbidden_grpc_response(
+    rejection_status: StatusCode,
+    message: &'static str,
+) -> tonic::codegen::http::Response<BoxBody> {
+    let code = match rejection_status {
+        StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+        StatusCode::NOT_FOUND => Code::NotFound,
+        _ => Code::PermissionDenied,
+    };
+    grpc_error_response(code, message)
+}
+
+/// Trailers-only response rejecting a gRPC request with the given status
+///
+/// The HTTP status is always 200 OK, with `grpc-status` and `grpc-message` sent along with the
+/// headers in the single frame that ends the stream. gRPC clients report any other HTTP status
+/// as a transport error rather than as the given status.
+fn grpc_error_response(
+    code: Code,
+    message: &'static str,
+) -> tonic::codegen::http::Response<BoxBody> {
+    Status::new(code, message).to_http()
+}
+
 impl ApiKeyMiddlewareLayer {
-    pub fn new(api_key: String) -> Self {
-        Self { api_key }
+    /// `read_only_denied_methods` are read methods that read-only api-keys may not call, as
+    /// `{package}.{Service}/{Method}`.
+    pub fn new(
+        auth_keys: AuthKeysHandle,
+        key_sources: Arc<ApiKeySources>,
+        unauthenticated_paths: Arc<UnauthenticatedPaths>,
+        rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
+        ip_allowlist: Option<Arc<IpAllowlist>>,
+        rejection_status: StatusCode,
+        read_only_denied_methods: &[String],
+    ) -> Self {
+        Self {
+            auth_keys,
+            key_sources,
+            unauthenticated_paths,
+            rate_limiter,
+            ip_allowlist,
+            rejection_status,
+            read_only_denied_methods: Arc::new(read_only_denied_methods.iter().cloned().collect()),
+        }
     }
 }
 
@@ -79,7 +501,311 @@ impl<S> Layer<S> for ApiKeyMiddlewareLayer {
     fn layer(&self, service: S) -> Self::Service {
         ApiKeyMiddleware {
             service,
-            api_key: self.api_key.clone(),
+            auth_keys: self.auth_keys.clone(),
+            key_sources: self.key_sources.clone(),
+            unauthenticated_paths: self.unauthenticated_paths.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            ip_allowlist: self.ip_allowlist.clone(),
+            rejection_status: self.rejection_status,
+            read_only_denied_methods: self.read_only_denied_methods.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::fmt::Debug;
+
+    use tonic::codegen::http::{Request, Response};
+    use tonic::transport::Body;
+
+    use super::*;
+    use crate::common::auth::{ApiKeys, AuthKeys};
+
+    async fn call_code<S>(service: &mut S, path: &str, key: Option<&str>) -> Code
+    where
+        S: Service<Request<Body>, Response = Response<BoxBody>>,
+        S::Error: Debug,
+    {
+        let mut request = Request::builder().uri(path);
+        if let Some(key) = key {
+            request = request.header("api-key", key);
+        }
+        let request = request.body(Body::empty()).unwrap();
+        grpc_code(&service.call(request).await.unwrap())
+    }
+
+    /// gRPC status of a trailers-only response, `Ok` if the service responded normally
+    fn grpc_code(response: &Response<BoxBody>) -> Code {
+        assert_eq!(response.status(), StatusCode::OK);
+        match response.headers().get("grpc-status") {
+            Some(status) => Code::from_bytes(status.as_bytes()),
+            None => Code::Ok,
+        }
+    }
+
+    #[test]
+    fn test_read_only_grpc_methods_sorted() {
+        assert!(READ_ONLY_GRPC_METHODS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_admin_grpc_methods_sorted() {
+        assert!(ADMIN_GRPC_METHODS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_grpc_method_access() {
+        let access = |path| grpc_method_access(path, &HashSet::new());
+        assert_eq!(access("/qdrant.Points/Search"), Access::Read);
+        assert_eq!(access("/qdrant.Points/Scroll"), Access::Read);
+        assert_eq!(access("/qdrant.Collections/Get"), Access::Read);
+        assert_eq!(access("/qdrant.Qdrant/HealthCheck"), Access::Read);
+        assert_eq!(access("/qdrant.Points/Upsert"), Access::Write);
+        assert_eq!(access("/qdrant.Points/Delete"), Access::Write);
+        assert_eq!(access("/qdrant.Points/CreateFieldIndex"), Access::Write);
+        assert_eq!(access("/qdrant.Collections/Delete"), Access::Admin);
+        assert_eq!(access("/qdrant.Snapshots/Create"), Access::Admin);
+        assert_eq!(access("/qdrant.Points/Unknown"), Access::Write);
+        assert_eq!(access(""), Access::Write);
+
+        // Read methods configured as denied to read-only keys require write access
+        let denied = HashSet::from(["qdrant.Points/Scroll".to_string()]);
+        assert_eq!(
+            grpc_method_access("/qdrant.Points/Scroll", &denied),
+            Access::Write,
+        );
+        assert_eq!(
+            grpc_method_access("/qdrant.Points/Search", &denied),
+            Access::Read,
+        );
+    }
+
+    #[test]
+    fn test_forbidden_grpc_response_status() {
+        let code = |status| grpc_code(&forbidden_grpc_response(status, "Invalid api-key"));
+        assert_eq!(code(StatusCode::FORBIDDEN), Code::PermissionDenied);
+        assert_eq!(code(StatusCode::UNAUTHORIZED), Code::Unauthenticated);
+        assert_eq!(code(StatusCode::NOT_FOUND), Code::NotFound);
+    }
+
+    #[test]
+    fn test_invalid_token_response_status() {
+        // Also used for tokens lacking the required access, regardless of the rejection status
+        for rejection_status in [StatusCode::FORBIDDEN, StatusCode::NOT_FOUND] {
+            let outcome = AuthOutcome::from(AuthError::InvalidToken);
+            let response = rejection_response(outcome, rejection_status).unwrap();
+            assert_eq!(grpc_code(&response), Code::Unauthenticated);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forwards_authenticated_requests() {
+        let auth_keys = AuthKeysHandle::new(AuthKeys::new(
+            ApiKeys::new(vec!["read-write".into()]),
+            ApiKeys::new(vec!["read-only".into()]),
+        ));
+        let key_sources = Arc::new(ApiKeySources {
+            header_name: "api-key".into(),
+            bearer: true,
+            basic: true,
+            query: false,
+            max_length: 1024,
+        });
+        let layer = ApiKeyMiddlewareLayer::new(
+            auth_keys,
+            key_sources,
+            Arc::default(),
+            None,
+            None,
+            StatusCode::FORBIDDEN,
+            &["qdrant.Points/Scroll".to_string()],
+        );
+        let mut service = layer.layer(tower::service_fn(|_| async {
+            Ok::<_, Infallible>(Response::new(BoxBody::default()))
+        }));
+
+        let search = "/qdrant.Points/Search";
+        let scroll = "/qdrant.Points/Scroll";
+        let upsert = "/qdrant.Points/Upsert";
+
+        // Read-write key may call everything
+        assert_eq!(
+            call_code(&mut service, search, Some("read-write")).await,
+            Code::Ok,
+        );
+        assert_eq!(
+            call_code(&mut service, upsert, Some("read-write")).await,
+            Code::Ok,
+        );
+
+        // Read-only key may only call read methods
+        assert_eq!(
+            call_code(&mut service, search, Some("read-only")).await,
+            Code::Ok,
+        );
+        assert_eq!(
+            call_code(&mut service, upsert, Some("read-only")).await,
+            Code::PermissionDenied,
+        );
+
+        // Read method denied to read-only keys by configuration
+        assert_eq!(
+            call_code(&mut service, scroll, Some("read-only")).await,
+            Code::PermissionDenied,
+        );
+        assert_eq!(
+            call_code(&mut service, scroll, Some("read-write")).await,
+            Code::Ok,
+        );
+
+        assert_eq!(
+            call_code(&mut service, search, Some("other")).await,
+            Code::PermissionDenied,
+        );
+        assert_eq!(
+            call_code(&mut service, search, None).await,
+            Code::PermissionDenied,
+        );
+
+        // Key given as bearer token
+        let request = Request::builder()
+            .uri(upsert)
+            .header(header::AUTHORIZATION, "Bearer read-write")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(grpc_code(&service.call(request).await.unwrap()), Code::Ok);
+
+        // Key given as Basic auth password, and malformed Basic auth credentials
+        for (authorization, expected) in [
+            ("Basic dXNlcjpyZWFkLXdyaXRl", Code::Ok),
+            ("Basic not-base64", Code::PermissionDenied),
+        ] {
+            let request = Request::builder()
+                .uri(upsert)
+                .header(header::AUTHORIZATION, authorization)
+                .body(Body::empty())
+                .unwrap();
+            assert_eq!(grpc_code(&service.call(request).await.unwrap()), expected);
         }
     }
+
+    #[tokio::test]
+    async fn test_request_id() {
+        let auth_keys = AuthKeysHandle::new(AuthKeys::new(
+            ApiKeys::new(vec!["key".into()]),
+            ApiKeys::default(),
+        ));
+        let key_sources = Arc::new(ApiKeySources {
+            header_name: "api-key".into(),
+            bearer: false,
+            basic: false,
+            query: false,
+            max_length: 1024,
+        });
+        let layer = ApiKeyMiddlewareLayer::new(
+            auth_keys,
+            key_sources,
+            Arc::default(),
+            None,
+            None,
+            StatusCode::FORBIDDEN,
+            &[],
+        );
+        // Responds with the request id seen by the service
+        let mut service = layer.layer(tower::service_fn(|request: Request<Body>| async move {
+            let request_id = request.extensions().get::<RequestId>().unwrap();
+            let mut response = Response::new(BoxBody::default());
+            response
+                .headers_mut()
+                .insert("seen-request-id", request_id.as_str().parse().unwrap());
+            Ok::<_, Infallible>(response)
+        }));
+
+        // Id of the client is propagated to the service and the response
+        let request = Request::builder()
+            .uri("/qdrant.Points/Search")
+            .header("api-key", "key")
+            .header(REQUEST_ID_HEADER, "proxy-1234")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "proxy-1234");
+        assert_eq!(response.headers()["seen-request-id"], "proxy-1234");
+
+        // Otherwise an id is generated, also for rejected requests
+        let request = Request::builder()
+            .uri("/qdrant.Points/Search")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        assert_eq!(grpc_code(&response), Code::PermissionDenied);
+        let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(request_id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_client_receives_status() {
+        use ::api::grpc::qdrant::qdrant_client::QdrantClient;
+        use ::api::grpc::qdrant::qdrant_server::QdrantServer;
+        use ::api::grpc::qdrant::HealthCheckRequest;
+        use tonic::transport::Server;
+
+        use crate::tonic::QdrantService;
+
+        let auth_keys = AuthKeysHandle::new(AuthKeys::new(
+            ApiKeys::new(vec!["read-write".into()]),
+            ApiKeys::default(),
+        ));
+        let key_sources = Arc::new(ApiKeySources {
+            header_name: "api-key".into(),
+            bearer: false,
+            basic: false,
+            query: false,
+            max_length: 1024,
+        });
+        let layer = ApiKeyMiddlewareLayer::new(
+            auth_keys,
+            key_sources,
+            Arc::default(),
+            None,
+            None,
+            StatusCode::FORBIDDEN,
+            &[],
+        );
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = tokio::spawn(
+            Server::builder()
+                .layer(layer)
+                .add_service(QdrantServer::new(QdrantService::default()))
+                .serve(addr),
+        );
+
+        let mut client = loop {
+            match QdrantClient::connect(format!("http://{addr}")).await {
+                Ok(client) => break client,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+
+        let status = client
+            .health_check(HealthCheckRequest {})
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(status.message(), "Invalid api-key");
+
+        let mut request = tonic::Request::new(HealthCheckRequest {});
+        request
+            .metadata_mut()
+            .insert("api-key", "read-write".parse().unwrap());
+        assert!(client.health_check(request).await.is_ok());
+
+        server.abort();
+    }
 }
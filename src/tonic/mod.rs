@@ -15,6 +15,7 @@ use ::api::grpc::qdrant::points_server::PointsServer;
 use ::api::grpc::qdrant::qdrant_server::{Qdrant, QdrantServer};
 use ::api::grpc::qdrant::snapshots_server::SnapshotsServer;
 use ::api::grpc::qdrant::{HealthCheckReply, HealthCheckRequest};
+use reqwest::StatusCode;
 use storage::content_manager::consensus_manager::ConsensusStateRef;
 use storage::content_manager::toc::TableOfContent;
 use storage::dispatcher::Dispatcher;
@@ -24,9 +25,12 @@ use tonic::codec::CompressionEncoding;
 use tonic::transport::{Server, ServerTlsConfig};
 use tonic::{Request, Response, Status};
 
+use crate::common::auth::{ApiKeySources, AuthKeysHandle, RateLimitKey, UnauthenticatedPaths};
 use crate::common::helpers;
+use crate::common::ip_allowlist::IpAllowlist;
+use crate::common::rate_limiter::RateLimiter;
 use crate::common::telemetry_ops::requests_telemetry::TonicTelemetryCollector;
-use crate::settings::Settings;
+use crate::settings::{ServiceConfig, Settings, UnauthenticatedPathConfig};
 use crate::tonic::api::collections_api::CollectionsService;
 use crate::tonic::api::collections_internal_api::CollectionsInternalService;
 use crate::tonic::api::points_api::PointsService;
@@ -46,12 +50,34 @@ impl Qdrant for QdrantService {
     }
 }
 
+/// Unauthenticated paths, including allowlisted gRPC services if enabled
+///
+/// gRPC request paths have the form `/{package}.{Service}/{Method}`, so services are matched
+/// as prefixes of the path.
+fn unauthenticated_grpc_paths(service_config: &ServiceConfig) -> Vec<UnauthenticatedPathConfig> {
+    let mut paths = service_config.unauthenticated_paths.clone();
+    if service_config.allow_unauthenticated_grpc_services {
+        paths.extend(
+            service_config
+                .grpc_unauthenticated_services
+                .iter()
+                .map(|service| UnauthenticatedPathConfig {
+                    path: format!("/{service}"),
+                    prefix: true,
+                }),
+        );
+    }
+    paths
+}
+
 pub fn init(
     dispatcher: Arc<Dispatcher>,
     telemetry_collector: Arc<parking_lot::Mutex<TonicTelemetryCollector>>,
     settings: Settings,
     grpc_port: u16,
     runtime: Handle,
+    auth_keys: Option<AuthKeysHandle>,
+    rate_limiter: Option<Arc<RateLimiter<RateLimitKey>>>,
 ) -> io::Result<()> {
     runtime.block_on(async {
         let socket =
@@ -74,18 +100,38 @@ pub fn init(
                 .map_err(helpers::tonic_error_to_io_error)?;
         };
 
+        let key_sources = Arc::new(ApiKeySources::from_config(&settings.service)?);
+        let unauthenticated_paths = Arc::new(UnauthenticatedPaths::new(
+            unauthenticated_grpc_paths(&settings.service),
+        ));
+        let ip_allowlist = settings
+            .service
+            .write_ip_allowlist
+            .as_ref()
+            .map(IpAllowlist::from_config)
+            .transpose()?
+            .map(Arc::new);
+        let rejection_status = StatusCode::from_u16(settings.service.api_key_rejection_status)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let api_key_layer = auth_keys.map(|auth_keys| {
+            api_key::ApiKeyMiddlewareLayer::new(
+                auth_keys,
+                key_sources,
+                unauthenticated_paths,
+                rate_limiter,
+                ip_allowlist,
+                rejection_status,
+                &settings.service.grpc_read_only_denied_methods,
+            )
+        });
+
         // The stack of middleware that our service will be wrapped in
         let middleware_layer = tower::ServiceBuilder::new()
             .layer(logging::LoggingMiddlewareLayer::new())
             .layer(tonic_telemetry::TonicTelemetryLayer::new(
                 telemetry_collector,
             ))
-            .option_layer(
-                settings
-                    .service
-                    .api_key
-                    .map(api_key::ApiKeyMiddlewareLayer::new),
-            )
+            .option_layer(api_key_layer)
             .into_inner();
 
         server